@@ -0,0 +1,72 @@
+//! Parses a generated multi-megabyte document and reports throughput. Run with `cargo bench`.
+
+use std::time::{Duration, Instant};
+
+use rust_json_parser::{ParserOptions, lexer::Tokens, parse_json};
+
+const RUNS: usize = 30;
+
+/// Records mixing the kinds of tokens real exports are made of: keys, short and long strings,
+/// escapes, integers, floats, literals and nesting
+fn document(records: usize) -> String {
+    let mut out = String::from("[");
+    for i in 0..records {
+        if i > 0 {
+            out.push(',');
+        }
+        out.push_str(&format!(
+            "\n  {{\"id\": {}, \"name\": \"user {}\", \"email\": \"user{}@example.com\", \
+             \"score\": {}.{}e-3, \"active\": {}, \"manager\": null, \
+             \"bio\": \"Line one\\nLine \\\"two\\\" \\u00e9t\\u00e9 and some more text to lex\", \
+             \"tags\": [\"a\", \"b\", \"c\"], \"location\": {{\"lat\": -{}.25, \"lon\": {}.5}}}}",
+            i,
+            i,
+            i,
+            i % 1000,
+            i % 97,
+            i % 2 == 0,
+            i % 90,
+            i % 180
+        ));
+    }
+    out.push_str("\n]\n");
+    out
+}
+
+/// Best of several runs, as the fastest one is the least disturbed by everything else
+fn best_of(mut run: impl FnMut()) -> Duration {
+    (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            run();
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    let input = document(20_000);
+    let megabytes = input.len() as f64 / (1024.0 * 1024.0);
+    println!("{:.1} MB document", megabytes);
+
+    let options = ParserOptions::default();
+    let time = best_of(|| {
+        std::hint::black_box(Tokens::new(std::hint::black_box(&input), &options).count());
+    });
+    report("lexing", megabytes, time);
+
+    let time = best_of(|| {
+        std::hint::black_box(parse_json(std::hint::black_box(&input)).unwrap());
+    });
+    report("parse_json", megabytes, time);
+}
+
+fn report(what: &str, megabytes: f64, time: Duration) {
+    println!(
+        "{}: {:.1} ms, {:.0} MB/s",
+        what,
+        time.as_secs_f64() * 1000.0,
+        megabytes / time.as_secs_f64()
+    );
+}
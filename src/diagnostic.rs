@@ -0,0 +1,294 @@
+use std::fmt;
+use std::io::{self, IsTerminal};
+
+use crate::errors::ParserError;
+
+const RED: &str = "\x1b[31m";
+const RESET: &str = "\x1b[0m";
+
+/// Whether the renderer emits ANSI color codes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorChoice {
+    Always,
+    #[default]
+    Never,
+    /// Color when `Renderer::eprint` writes to a terminal and `NO_COLOR` is not set. Rendering
+    /// into a string or writer stays plain.
+    Auto,
+}
+
+impl ColorChoice {
+    fn enabled(self, to_stderr: bool) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                to_stderr && std::env::var_os("NO_COLOR").is_none() && io::stderr().is_terminal()
+            }
+        }
+    }
+}
+
+/// Renders a `ParserError` as a message followed by the offending source line and a caret
+/// under the problem:
+///
+/// ```text
+/// Parse error at line 3, column 15: Expected ',' or '}' in object but found ]
+///    3 |     "name": "John"]
+///      |               ^
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Renderer {
+    color: ColorChoice,
+    context_before: usize,
+    context_after: usize,
+    tab_width: usize,
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Self {
+            color: ColorChoice::Never,
+            context_before: 0,
+            context_after: 0,
+            tab_width: 4,
+        }
+    }
+}
+
+impl Renderer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn color(mut self, color: ColorChoice) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Number of source lines shown before and after the line with the error
+    pub fn context_lines(mut self, before: usize, after: usize) -> Self {
+        self.context_before = before;
+        self.context_after = after;
+        self
+    }
+
+    /// Tabs in the source are expanded to this many columns, so the caret lines up
+    pub fn tab_width(mut self, tab_width: usize) -> Self {
+        self.tab_width = tab_width.max(1);
+        self
+    }
+
+    pub fn render(&self, error: &ParserError, input: &str) -> String {
+        let mut out = String::new();
+        // Writing into a String never fails
+        self.render_to(&mut out, error, input)
+            .expect("writing to a String cannot fail");
+        out
+    }
+
+    pub fn write_to<W: io::Write>(
+        &self,
+        mut writer: W,
+        error: &ParserError,
+        input: &str,
+    ) -> io::Result<()> {
+        writer.write_all(self.render(error, input).as_bytes())
+    }
+
+    pub fn render_to<W: fmt::Write>(
+        &self,
+        out: &mut W,
+        error: &ParserError,
+        input: &str,
+    ) -> fmt::Result {
+        self.write(out, error, input, self.color.enabled(false))
+    }
+
+    /// Prints to stderr, the one place `ColorChoice::Auto` looks at the terminal
+    pub fn eprint(&self, error: &ParserError, input: &str) {
+        let mut out = String::new();
+        self.write(&mut out, error, input, self.color.enabled(true))
+            .expect("writing to a String cannot fail");
+        eprint!("{}", out);
+    }
+
+    fn write<W: fmt::Write>(
+        &self,
+        out: &mut W,
+        error: &ParserError,
+        input: &str,
+        color: bool,
+    ) -> fmt::Result {
+        let (red, reset) = if color { (RED, RESET) } else { ("", "") };
+        match error {
+            ParserError::Parser { kind, .. } => writeln!(
+                out,
+                "{}Parse error at line {}, column {}: {}{}",
+                red,
+                error.line(),
+                error.column(),
+                kind,
+                reset
+            )?,
+            ParserError::Lexer(err) => writeln!(out, "{}Lexer error: {}{}", red, err, reset)?,
+        }
+
+        // The carets go under the span, which starts on the line shown in the middle
+        let span = error.span();
+        let (line, column) = (span.start_line, span.start_column);
+        let lines: Vec<&str> = input.lines().collect();
+        if line == 0 || line > lines.len() {
+            return Ok(());
+        }
+        let first = line.saturating_sub(self.context_before).max(1);
+        let last = (line + self.context_after).min(lines.len());
+        let gutter = last.to_string().len().max(4);
+
+        for number in first..=last {
+            let code_line = lines[number - 1];
+            writeln!(
+                out,
+                "{:>gutter$} | {}",
+                number,
+                self.expand_tabs(code_line),
+                gutter = gutter
+            )?;
+            if number == line {
+                // A span running onto later lines is marked up to the end of this one
+                let len = if span.end_line == line {
+                    span.end_column - column
+                } else {
+                    code_line.chars().count().saturating_sub(column - 1)
+                };
+                let (start, width) = self.caret_position(code_line, column, len);
+                writeln!(
+                    out,
+                    "{:gutter$} | {:start$}{}{}{}",
+                    "",
+                    "",
+                    red,
+                    "^".repeat(width),
+                    reset,
+                    gutter = gutter,
+                    start = start
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    fn expand_tabs(&self, line: &str) -> String {
+        let mut expanded = String::with_capacity(line.len());
+        let mut width = 0;
+        for c in line.chars() {
+            if c == '\t' {
+                let stop = self.next_tab_stop(width);
+                expanded.extend(std::iter::repeat_n(' ', stop - width));
+                width = stop;
+            } else {
+                expanded.push(c);
+                width += 1;
+            }
+        }
+        expanded
+    }
+
+    /// Display column where the caret starts and how many carets to draw, given the 1-based
+    /// char column of the error and the number of chars to highlight
+    fn caret_position(&self, line: &str, column: usize, len: usize) -> (usize, usize) {
+        let mut width = 0;
+        let mut start = None;
+        for (i, c) in line.chars().enumerate() {
+            if i == column - 1 {
+                start = Some(width);
+            }
+            if i == column - 1 + len.max(1) {
+                break;
+            }
+            width = if c == '\t' {
+                self.next_tab_stop(width)
+            } else {
+                width + 1
+            };
+        }
+        match start {
+            Some(start) => (start, (width - start).max(1)),
+            // The error sits past the end of the line, such as an unexpected end of input
+            None => (width + column - 1 - line.chars().count(), 1),
+        }
+    }
+
+    fn next_tab_stop(&self, width: usize) -> usize {
+        (width / self.tab_width + 1) * self.tab_width
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_json;
+
+    fn render(renderer: Renderer, input: &str) -> String {
+        let error = parse_json(input).unwrap_err();
+        renderer.color(ColorChoice::Never).render(&error, input)
+    }
+
+    #[test]
+    fn test_render_plain() {
+        let input = "{\n  \"a\": 1,\n  \"b\": tru\n}";
+        assert_eq!(
+            render(Renderer::new(), input),
+            "Lexer error: 3:11: Invalid literal\n   3 |   \"b\": tru\n     |        ^^^\n"
+        );
+    }
+
+    #[test]
+    fn test_render_context_lines() {
+        let input = "{\n  \"a\": 1,\n  \"b\": 2]\n}";
+        assert_eq!(
+            render(Renderer::new().context_lines(1, 1), input),
+            "Parse error at line 3, column 9: Expected ',' or '}' in object but found ]\n   \
+             2 |   \"a\": 1,\n   \
+             3 |   \"b\": 2]\n     \
+             |         ^\n   \
+             4 | }\n"
+        );
+    }
+
+    #[test]
+    fn test_render_aligns_with_tabs() {
+        let input = "[\n\t\t1,\n\t\t\"x\" \"yz\"]";
+        let rendered = render(Renderer::new().tab_width(4), input);
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert_eq!(lines[1], "   3 |         \"x\" \"yz\"]");
+        assert_eq!(lines[2], "     |             ^^^^");
+    }
+
+    #[test]
+    fn test_render_highlights_whole_token() {
+        let rendered = render(Renderer::new(), "[1 2.50e3]");
+        assert_eq!(rendered.lines().nth(2), Some("     |    ^^^^^^"));
+        // Escaped strings are marked as written, not by the length of their value
+        let rendered = render(Renderer::new(), r#"[1 "\u00e9t\u00e9"]"#);
+        assert_eq!(rendered.lines().nth(2), Some("     |    ^^^^^^^^^^^^^^^"));
+    }
+
+    #[test]
+    fn test_render_color() {
+        let input = "[1,]";
+        let error = parse_json(input).unwrap_err();
+        let rendered = Renderer::new()
+            .color(ColorChoice::Always)
+            .render(&error, input);
+        assert!(rendered.starts_with("\x1b[31mParse error"));
+        assert!(rendered.ends_with("\x1b[31m^\x1b[0m\n"));
+        // Only stderr gets to decide for itself
+        let plain = Renderer::new().render(&error, input);
+        let auto = Renderer::new()
+            .color(ColorChoice::Auto)
+            .render(&error, input);
+        assert!(!plain.contains('\x1b'));
+        assert_eq!(auto, plain);
+    }
+}
@@ -0,0 +1,293 @@
+use std::io::Read;
+
+use crate::{
+    errors::{LexerError, ParserError, ReadError},
+    jsonvalue::JSONValue,
+    lexer::{Token, Tokens},
+    options::ParserOptions,
+    pull::{Event, PullParser},
+    reader::ReadLexer,
+    spanned::{array_index, unescape_pointer_token},
+};
+
+/// Iterates over the elements of one array in a document, such as an export that is a single
+/// large array of records. Each element is parsed when it is asked for and handed over, so only
+/// one is held at a time rather than the whole array.
+///
+/// By default the elements are those of the array the document consists of, `pointer` selects
+/// one deeper inside. The rest of the document is checked as well, so errors after the array
+/// come up once its elements are done, and end the iteration like in `PullParser`.
+pub struct ArrayElements<'a> {
+    elements: Elements<'a, Tokens<'a>>,
+}
+
+/// Like `ArrayElements`, but reads the document from an `io::Read` while iterating, see
+/// `parse_reader_with`. Elements own their strings, as the input is gone once read.
+pub struct ArrayElementsReader<R: Read> {
+    elements: Elements<'static, ReadLexer<R>>,
+}
+
+/// What both iterators share: finding the array and parsing its elements
+struct Elements<'a, I>
+where
+    I: Iterator<Item = Result<Token<'a>, LexerError>>,
+{
+    parser: PullParser<'a, I>,
+    pointer: String,
+    state: State,
+}
+
+enum State {
+    /// Looking for the array the pointer leads to
+    Seeking,
+    /// Inside the array, between elements
+    Inside,
+    Done,
+}
+
+impl<'a, I> Elements<'a, I>
+where
+    I: Iterator<Item = Result<Token<'a>, LexerError>>,
+{
+    fn new(parser: PullParser<'a, I>) -> Self {
+        Self {
+            parser,
+            pointer: String::new(),
+            state: State::Seeking,
+        }
+    }
+
+    fn next(&mut self) -> Option<Result<JSONValue<'a>, ParserError>> {
+        match self.next_element() {
+            Ok(Some(value)) => Some(Ok(value)),
+            Ok(None) => None,
+            Err(error) => {
+                self.state = State::Done;
+                Some(Err(error))
+            }
+        }
+    }
+
+    fn next_element(&mut self) -> Result<Option<JSONValue<'a>>, ParserError> {
+        loop {
+            match self.state {
+                State::Seeking => match self.seek()? {
+                    true => self.state = State::Inside,
+                    false => self.finish()?,
+                },
+                State::Inside => {
+                    if let Some(value) = self.parser.read_value()? {
+                        return Ok(Some(value));
+                    }
+                    self.finish()?;
+                }
+                State::Done => return Ok(None),
+            }
+        }
+    }
+
+    /// Walks to the array the pointer leads to, stopping right after its `[`. False when the
+    /// pointer leads nowhere, or to something that is not an array.
+    fn seek(&mut self) -> Result<bool, ParserError> {
+        let pointer = std::mem::take(&mut self.pointer);
+        if !pointer.is_empty() {
+            let Some(path) = pointer.strip_prefix('/') else {
+                return Ok(false);
+            };
+            for token in path.split('/') {
+                if !self.enter(&unescape_pointer_token(token))? {
+                    return Ok(false);
+                }
+            }
+        }
+        Ok(matches!(
+            self.parser.next_event()?,
+            Some((Event::BeginArray, _))
+        ))
+    }
+
+    /// Moves into the next value up to the member or element a pointer token names. Objects
+    /// are entered at the first member with that key.
+    fn enter(&mut self, token: &str) -> Result<bool, ParserError> {
+        match self.parser.next_event()? {
+            Some((Event::BeginObject, _)) => loop {
+                match self.parser.next_event()? {
+                    Some((Event::Key(key), _)) if key == token => return Ok(true),
+                    Some((Event::Key(_), _)) => {
+                        self.parser.skip_value()?;
+                    }
+                    _ => return Ok(false),
+                }
+            },
+            Some((Event::BeginArray, _)) => {
+                let Some(index) = array_index(token) else {
+                    return Ok(false);
+                };
+                for _ in 0..index {
+                    if self.parser.skip_value()?.is_none() {
+                        return Ok(false);
+                    }
+                }
+                // The array may end right here, which the next event tells
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Checks whatever is left of the document
+    fn finish(&mut self) -> Result<(), ParserError> {
+        self.state = State::Done;
+        while self.parser.next_event()?.is_some() {}
+        Ok(())
+    }
+}
+
+impl<'a> ArrayElements<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Self::with_options(input, &ParserOptions::default())
+    }
+
+    pub fn with_options(input: &'a str, options: &ParserOptions) -> Self {
+        Self {
+            elements: Elements::new(PullParser::with_options(input, options)),
+        }
+    }
+
+    /// Iterate over the array a JSON Pointer (RFC 6901) leads to, such as `/data/items`.
+    /// Nothing is yielded when it leads nowhere or to something other than an array.
+    ///
+    /// Of repeated keys the pointer follows the first, as the document is read front to back,
+    /// while `parse_json` keeps the last by default. With `DuplicateKeys::Error` the repeated
+    /// key fails once it comes up, after the elements of the first.
+    pub fn pointer(mut self, pointer: &str) -> Self {
+        self.elements.pointer = pointer.to_string();
+        self
+    }
+}
+
+impl<'a> Iterator for ArrayElements<'a> {
+    type Item = Result<JSONValue<'a>, ParserError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.elements.next()
+    }
+}
+
+impl<R: Read> ArrayElementsReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self::with_options(reader, &ParserOptions::default())
+    }
+
+    pub fn with_options(reader: R, options: &ParserOptions) -> Self {
+        let lexer = ReadLexer::new(reader, options);
+        Self {
+            elements: Elements::new(PullParser::from_tokens(lexer)),
+        }
+    }
+
+    /// See `ArrayElements::pointer`
+    pub fn pointer(mut self, pointer: &str) -> Self {
+        self.elements.pointer = pointer.to_string();
+        self
+    }
+}
+
+impl<R: Read> Iterator for ArrayElementsReader<R> {
+    type Item = Result<JSONValue<'static>, ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.elements.next()?.map_err(ReadError::from))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::DuplicateKeys;
+
+    fn collect<'a>(
+        values: impl Iterator<Item = Result<JSONValue<'a>, ParserError>>,
+    ) -> Vec<String> {
+        values
+            .map(|result| match result {
+                Ok(value) => value.to_string(),
+                Err(e) => e.code().to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_top_level_elements() {
+        let input = r#"[{"id": 1, "tags": ["a"]}, 2, "x", [], {}]"#;
+        assert_eq!(
+            collect(ArrayElements::new(input)),
+            [r#"{"id":1,"tags":["a"]}"#, "2", "\"x\"", "[]", "{}"]
+        );
+        assert!(ArrayElements::new(" [ ] ").next().is_none());
+        assert!(ArrayElements::new(r#"{"a": [1]}"#).next().is_none());
+
+        // The rest of the document is checked too
+        assert_eq!(
+            collect(ArrayElements::new("[1, 2] 3")),
+            ["1", "2", "expected_token"]
+        );
+        assert_eq!(
+            collect(ArrayElements::new("[1, 2,]")),
+            ["1", "2", "trailing_comma"]
+        );
+        assert_eq!(collect(ArrayElements::new("7 [1]")), ["expected_token"]);
+    }
+
+    #[test]
+    fn test_pointer_selects_array() {
+        let input =
+            r#"{"meta": {"items": [0]}, "data": [{"items": [1, {"a": 2}]}, {"items": [3]}]}"#;
+        let elements = |pointer| collect(ArrayElements::new(input).pointer(pointer));
+        assert_eq!(elements("/data/0/items"), ["1", r#"{"a":2}"#]);
+        assert_eq!(elements("/data/1/items"), ["3"]);
+        assert_eq!(elements("/meta/items"), ["0"]);
+        assert_eq!(
+            elements("/data"),
+            [r#"{"items":[1,{"a":2}]}"#, r#"{"items":[3]}"#]
+        );
+        for nowhere in [
+            "/data/2/items",
+            "/data/0/items/0",
+            "/nothing",
+            "/data/01",
+            "data",
+        ] {
+            assert!(elements(nowhere).is_empty(), "{}", nowhere);
+        }
+
+        // Repeated keys lead to the first of them, or fail when they are an error
+        let input = r#"{"a": [1], "a": [2]}"#;
+        assert_eq!(collect(ArrayElements::new(input).pointer("/a")), ["1"]);
+        let options = ParserOptions::new().duplicate_keys(DuplicateKeys::Error);
+        assert_eq!(
+            collect(ArrayElements::with_options(input, &options).pointer("/a")),
+            ["1", "duplicate_key"]
+        );
+
+        let input = r#"{"a/b": {"~": [true]}}"#;
+        assert_eq!(
+            collect(ArrayElements::new(input).pointer("/a~1b/~0")),
+            ["true"]
+        );
+    }
+
+    #[test]
+    fn test_reader() {
+        let input = r#"{"rows": [{"name": "é"}, [null]]}"#;
+        let values: Vec<_> = ArrayElementsReader::new(input.as_bytes())
+            .pointer("/rows")
+            .map(|result| result.unwrap().to_string())
+            .collect();
+        assert_eq!(values, [r#"{"name":"é"}"#, "[null]"]);
+
+        let mut elements = ArrayElementsReader::new(&b"[1, \"\xff\"]"[..]);
+        assert!(matches!(elements.next(), Some(Err(ReadError::Io(_)))));
+        assert!(elements.next().is_none());
+    }
+}
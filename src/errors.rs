@@ -1,24 +1,37 @@
+use crate::diagnostic::{ColorChoice, Renderer};
 use crate::jsonvalue::TokenKind;
+use crate::span::Span;
 use std::fmt;
+use std::io;
+use std::mem;
+use std::sync::Arc;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum ParserErrorKind {
-    UnexpectedToken(TokenKind),
+    UnexpectedToken(TokenKind<'static>),
     UnexpectedEOF,
     // you can add "ExpectedButFound" variants for richer errors
-    ExpectedToken(TokenKind, TokenKind), // expected, found
-    ExpectedOneOfTokens(Vec<TokenKind>, TokenKind), // expected, found
-    ExpectedObjectEndOrComma(TokenKind), // found
+    ExpectedToken(TokenKind<'static>, TokenKind<'static>), // expected, found
+    ExpectedOneOfTokens(Vec<TokenKind<'static>>, TokenKind<'static>), // expected, found
+    ExpectedObjectEndOrComma(TokenKind<'static>),          // found
     TrailingComma,
+    DepthLimitExceeded(usize), // the limit
+    DuplicateKey {
+        key: String,
+        first_line: usize,
+        first_column: usize,
+    },
+    TruncatedRecord,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum ParserError {
     Lexer(LexerError),
     Parser {
-        kind: ParserErrorKind,
-        line: usize,
-        column: usize,
+        /// Boxed to keep `Result`s small, the span already takes up most of the error
+        kind: Box<ParserErrorKind>,
+        /// The token the error is about, the error's line and column are where it starts
+        span: Span,
     },
 }
 
@@ -36,6 +49,24 @@ impl fmt::Display for ParserErrorKind {
                 write!(f, "Expected ',' or '}}' in object but found {}", found)
             }
             ParserErrorKind::TrailingComma => write!(f, "Trailing comma found"),
+            ParserErrorKind::DepthLimitExceeded(limit) => {
+                write!(f, "Arrays and objects nested deeper than {} levels", limit)
+            }
+            ParserErrorKind::DuplicateKey {
+                key,
+                first_line,
+                first_column,
+            } => write!(
+                f,
+                "Duplicate key \"{}\", first defined at {}:{}",
+                key, first_line, first_column
+            ),
+            ParserErrorKind::TruncatedRecord => {
+                write!(
+                    f,
+                    "Record ends right after a number or literal, which may be cut short"
+                )
+            }
             ParserErrorKind::ExpectedOneOfTokens(expected, found) => {
                 let expected_list = expected
                     .iter()
@@ -52,56 +83,176 @@ impl fmt::Display for ParserError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             ParserError::Lexer(e) => write!(f, "{}", e),
-            ParserError::Parser { kind, line, column } => {
-                write!(f, "{}:{}: {}", line, column, kind)
+            ParserError::Parser { kind, span } => {
+                write!(f, "{}:{}: {}", span.start_line, span.start_column, kind)
             }
         }
     }
 }
 
+impl ParserErrorKind {
+    /// Stable identifier for the kind of error, meant for tools rather than people.
+    /// Codes never change once released, unlike the wording of the messages.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParserErrorKind::UnexpectedToken(_) => "unexpected_token",
+            ParserErrorKind::UnexpectedEOF => "unexpected_eof",
+            ParserErrorKind::ExpectedToken(_, _) => "expected_token",
+            ParserErrorKind::ExpectedOneOfTokens(_, _) => "expected_one_of_tokens",
+            ParserErrorKind::ExpectedObjectEndOrComma(_) => "expected_object_end_or_comma",
+            ParserErrorKind::TrailingComma => "trailing_comma",
+            ParserErrorKind::DepthLimitExceeded(_) => "depth_limit_exceeded",
+            ParserErrorKind::DuplicateKey { .. } => "duplicate_key",
+            ParserErrorKind::TruncatedRecord => "truncated_record",
+        }
+    }
+}
+
+// Wrapped errors are displayed as they are, so they are not their source as well, which would
+// print them twice in an error chain
+impl std::error::Error for ParserError {}
+
+impl From<LexerError> for ParserError {
+    fn from(error: LexerError) -> Self {
+        ParserError::Lexer(error)
+    }
+}
+
 impl ParserError {
+    pub fn line(&self) -> usize {
+        match self {
+            ParserError::Lexer(e) => e.line,
+            ParserError::Parser { span, .. } => span.start_line,
+        }
+    }
+
+    pub fn column(&self) -> usize {
+        match self {
+            ParserError::Lexer(e) => e.column,
+            ParserError::Parser { span, .. } => span.start_column,
+        }
+    }
+
+    /// Byte range and positions of the offending input
+    pub fn span(&self) -> Span {
+        match self {
+            ParserError::Lexer(e) => e.span,
+            ParserError::Parser { span, .. } => *span,
+        }
+    }
+
+    /// The code of the underlying lexer or parser error kind
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParserError::Lexer(e) => e.kind.code(),
+            ParserError::Parser { kind, .. } => kind.code(),
+        }
+    }
+
+    /// Prints the error with the offending line to stderr, see `diagnostic::Renderer` to
+    /// render into a String or any writer instead
     pub fn pretty_print(&self, input: &str) {
-        let lines: Vec<&str> = input.lines().collect();
+        Renderer::new().color(ColorChoice::Auto).eprint(self, input);
+    }
 
+    /// Moves every position in the error forward, see `Span::offset`
+    pub(crate) fn offset(self, bytes: usize, lines: usize) -> Self {
         match self {
-            ParserError::Parser { kind, line, column } => {
-                let token_len = match kind {
-                    ParserErrorKind::UnexpectedToken(tok) => tok.display_len(),
-                    ParserErrorKind::ExpectedToken(_, tok) => tok.display_len(),
-                    ParserErrorKind::ExpectedObjectEndOrComma(tok) => tok.display_len(),
-                    ParserErrorKind::UnexpectedEOF => 1,
-                    ParserErrorKind::TrailingComma => 1,
-                    ParserErrorKind::ExpectedOneOfTokens(_, tok) => tok.display_len(),
-                };
-
-                eprintln!(
-                    "\x1b[31mParse error at line {}, column {}: {}\x1b[0m",
-                    line, column, kind
-                );
-
-                if let Some(code_line) = lines.get(line - 1) {
-                    eprintln!("{:>4} | {}", line, code_line);
-                    eprintln!(
-                        "     | {:>width$}{}",
-                        "",
-                        "^".repeat(token_len.max(1)),
-                        width = column - 1
-                    );
+            ParserError::Lexer(e) => ParserError::Lexer(LexerError {
+                line: e.line + lines,
+                span: e.span.offset(bytes, lines),
+                ..e
+            }),
+            ParserError::Parser { mut kind, span } => {
+                if let ParserErrorKind::DuplicateKey { first_line, .. } = kind.as_mut() {
+                    *first_line += lines;
                 }
-            }
-            ParserError::Lexer(err) => {
-                eprintln!("\x1b[31mLexer error: {}\x1b[0m", err);
-
-                if let Some(code_line) = lines.get(err.line - 1) {
-                    eprintln!("{:>4} | {}", err.line, code_line);
-                    eprintln!("     | {:>width$}^", "", width = err.column - 1);
+                ParserError::Parser {
+                    kind,
+                    span: span.offset(bytes, lines),
                 }
             }
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// Error from parsing input that is read from an `io::Read`, which can fail on its own
+#[derive(Debug, Clone)]
+pub enum ReadError {
+    /// Reading failed, or the input was not valid UTF-8. Shared, so the error can be cloned.
+    Io(Arc<io::Error>),
+    Parse(ParserError),
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReadError::Io(e) => write!(f, "I/O error: {}", e),
+            ReadError::Parse(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ReadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReadError::Io(e) => Some(e.as_ref()),
+            ReadError::Parse(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for ReadError {
+    fn from(error: io::Error) -> Self {
+        ReadError::Io(Arc::new(error))
+    }
+}
+
+impl From<ParserError> for ReadError {
+    fn from(error: ParserError) -> Self {
+        match error {
+            ParserError::Lexer(LexerError {
+                kind: LexerErrorKinds::Io(error),
+                ..
+            }) => ReadError::Io(error),
+            error => ReadError::Parse(error),
+        }
+    }
+}
+
+/// Error from walking a document with a `Handler`, which can stop the walk with its own error
+#[derive(Debug)]
+pub enum HandlerError<E> {
+    Parse(ParserError),
+    /// What the handler returned
+    Handler(E),
+}
+
+impl<E: fmt::Display> fmt::Display for HandlerError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HandlerError::Parse(e) => write!(f, "{}", e),
+            HandlerError::Handler(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for HandlerError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            HandlerError::Parse(e) => Some(e),
+            HandlerError::Handler(e) => Some(e),
+        }
+    }
+}
+
+impl<E> From<ParserError> for HandlerError<E> {
+    fn from(error: ParserError) -> Self {
+        HandlerError::Parse(error)
+    }
+}
+
+#[derive(Debug, Clone)]
 pub enum LexerErrorKinds {
     UnexcpectedChar(char),
     UnescapedControlCharacter,
@@ -114,6 +265,28 @@ pub enum LexerErrorKinds {
     InvalidExponent,
     CastingError,
     InvalidEscape,
+    LoneSurrogate,
+    UnterminatedComment,
+    /// Reading the input failed or it was not valid UTF-8. Only comes from input that is read,
+    /// which returns it as `ReadError::Io`.
+    Io(Arc<io::Error>),
+}
+
+impl PartialEq for LexerErrorKinds {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (LexerErrorKinds::UnexcpectedChar(a), LexerErrorKinds::UnexcpectedChar(b))
+            | (LexerErrorKinds::InvalidEscapeChar(a), LexerErrorKinds::InvalidEscapeChar(b)) => {
+                a == b
+            }
+            // I/O errors cannot be compared, their kind and message can
+            (LexerErrorKinds::Io(a), LexerErrorKinds::Io(b)) => {
+                a.kind() == b.kind() && a.to_string() == b.to_string()
+            }
+            // The other kinds carry nothing
+            _ => mem::discriminant(self) == mem::discriminant(other),
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -121,12 +294,14 @@ pub struct LexerError {
     pub kind: LexerErrorKinds,
     pub line: usize,
     pub column: usize,
+    /// The lexeme read so far, up to and including the offending char
+    pub span: Span,
 }
 
 impl fmt::Display for LexerError {
     // TODO: Instead of returning to_string for each case, use write! macro to write directly to the formatter
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let description = match self.kind {
+        let description = match &self.kind {
             LexerErrorKinds::UnexcpectedChar(c) => format!("Unexpected character: '{}'", c),
             LexerErrorKinds::InvalidEscapeChar(c) => format!("Invalid escape character: '\\{}'", c),
             LexerErrorKinds::UnclosedString => "Unclosed string literal".to_string(),
@@ -137,14 +312,41 @@ impl fmt::Display for LexerError {
             LexerErrorKinds::CastingError => "Error casting number".to_string(),
             LexerErrorKinds::InvalidLiteral => "Invalid literal".to_string(),
             LexerErrorKinds::InvalidEscape => "Invalid escape sequence".to_string(),
+            LexerErrorKinds::LoneSurrogate => "Unpaired UTF-16 surrogate in \\u escape".to_string(),
             LexerErrorKinds::UnescapedControlCharacter => {
                 "Unescaped control character in string".to_string()
             }
+            LexerErrorKinds::UnterminatedComment => "Unterminated block comment".to_string(),
+            LexerErrorKinds::Io(error) => format!("Reading failed: {}", error),
         };
         write!(f, "{}:{}: {}", self.line, self.column, description)
     }
 }
 
+impl std::error::Error for LexerError {}
+
+impl LexerErrorKinds {
+    /// Stable identifier for the kind of error, see `ParserErrorKind::code`
+    pub fn code(&self) -> &'static str {
+        match self {
+            LexerErrorKinds::UnexcpectedChar(_) => "unexpected_char",
+            LexerErrorKinds::UnescapedControlCharacter => "unescaped_control_character",
+            LexerErrorKinds::InvalidEscapeChar(_) => "invalid_escape_char",
+            LexerErrorKinds::UnclosedString => "unclosed_string",
+            LexerErrorKinds::LeadingZero => "leading_zero",
+            LexerErrorKinds::InvalidNumber => "invalid_number",
+            LexerErrorKinds::InvalidLiteral => "invalid_literal",
+            LexerErrorKinds::InvalidDecimal => "invalid_decimal",
+            LexerErrorKinds::InvalidExponent => "invalid_exponent",
+            LexerErrorKinds::CastingError => "casting_error",
+            LexerErrorKinds::InvalidEscape => "invalid_escape",
+            LexerErrorKinds::LoneSurrogate => "lone_surrogate",
+            LexerErrorKinds::UnterminatedComment => "unterminated_comment",
+            LexerErrorKinds::Io(..) => "io_error",
+        }
+    }
+}
+
 // #[cfg(test)]
 // mod tests {
 //     use super::*;
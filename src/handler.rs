@@ -0,0 +1,224 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+};
+
+use crate::{
+    errors::{HandlerError, ParserError},
+    jsonvalue::JSONValue,
+    lexer::Lexer,
+    number::Number,
+    options::{DuplicateKeys, ParserOptions},
+    parser::{Parser, Sink},
+    span::Span,
+};
+
+/// Gets told about each part of a document in source order, for processing it without building
+/// a `JSONValue`. Every method does nothing by default, so implement the ones you need.
+///
+/// Returning an error from any of them stops parsing right away, `parse_json_events` then
+/// returns it as `HandlerError::Handler`.
+pub trait Handler<'a> {
+    type Error;
+
+    fn start_object(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Comes before the member's value. Keys are passed on as they come, repeated ones too,
+    /// unless `ParserOptions::duplicate_keys` makes them an error.
+    fn key(&mut self, _key: Cow<'a, str>) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn end_object(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn start_array(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn end_array(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Strings borrow from the input unless they hold escapes
+    fn string(&mut self, _value: Cow<'a, str>) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn number(&mut self, _value: Number<'a>) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn bool(&mut self, _value: bool) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn null(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// Parses a document, handing each part to `handler` instead of building a value. Memory use
+/// does not grow with the document, only with how deeply it nests.
+pub fn parse_json_events<'a, H: Handler<'a>>(
+    input: &'a str,
+    handler: &mut H,
+) -> Result<(), HandlerError<H::Error>> {
+    parse_json_events_with(input, &ParserOptions::default(), handler)
+}
+
+/// Parses a document into `handler` using the given options
+pub fn parse_json_events_with<'a, H: Handler<'a>>(
+    input: &'a str,
+    options: &ParserOptions,
+    handler: &mut H,
+) -> Result<(), HandlerError<H::Error>> {
+    let lexer = Lexer::with_options(input, options);
+    let mut parser = Parser::from_tokens(lexer);
+    parser.parse_events(handler)
+}
+
+/// Hands what the parser reads on to a handler, for `Parser::parse_events`
+pub(crate) struct Events<'h, H>(pub(crate) &'h mut H);
+
+impl<'a, H: Handler<'a>> Sink<'a> for Events<'_, H> {
+    type Value = ();
+    /// Where in the object each key is, only kept when duplicates are an error
+    type Members = HashMap<Cow<'a, str>, usize>;
+    type Error = HandlerError<H::Error>;
+
+    fn scalar(&mut self, value: JSONValue<'a>, _span: Span) -> Result<(), Self::Error> {
+        let result = match value {
+            JSONValue::String(s) => self.0.string(s),
+            JSONValue::Number(n) => self.0.number(n),
+            JSONValue::Bool(b) => self.0.bool(b),
+            JSONValue::Null => self.0.null(),
+            JSONValue::Array(_) | JSONValue::Object(_) => unreachable!("only scalars come here"),
+        };
+        result.map_err(HandlerError::Handler)
+    }
+
+    fn start_array(&mut self) -> Result<(), Self::Error> {
+        self.0.start_array().map_err(HandlerError::Handler)
+    }
+
+    fn array(&mut self, _items: Vec<()>, _span: Span) -> Result<(), Self::Error> {
+        self.0.end_array().map_err(HandlerError::Handler)
+    }
+
+    fn start_object(&mut self) -> Result<Self::Members, Self::Error> {
+        self.0.start_object().map_err(HandlerError::Handler)?;
+        Ok(HashMap::new())
+    }
+
+    fn index_of(object: &Self::Members, key: &str) -> Option<usize> {
+        object.get(key).copied()
+    }
+
+    fn key(&mut self, key: &Cow<'a, str>) -> Result<(), Self::Error> {
+        self.0.key(key.clone()).map_err(HandlerError::Handler)
+    }
+
+    fn insert(
+        &mut self,
+        object: &mut Self::Members,
+        (key, _span): (Cow<'a, str>, Span),
+        _value: (),
+        policy: DuplicateKeys,
+        _collected: &mut HashSet<usize>,
+    ) {
+        if policy == DuplicateKeys::Error {
+            let i = object.len();
+            object.entry(key).or_insert(i);
+        }
+    }
+
+    fn object(&mut self, _object: Self::Members, _span: Span) -> Result<(), Self::Error> {
+        self.0.end_object().map_err(HandlerError::Handler)
+    }
+
+    fn recoverable(error: Self::Error) -> Result<ParserError, Self::Error> {
+        match error {
+            HandlerError::Parse(error) => Ok(error),
+            error => Err(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Counts what it sees, and gives up on a key named "stop"
+    #[derive(Default)]
+    struct Stats {
+        events: Vec<String>,
+        depth: usize,
+        max_depth: usize,
+    }
+
+    impl<'a> Handler<'a> for Stats {
+        type Error = String;
+
+        fn start_object(&mut self) -> Result<(), String> {
+            self.depth += 1;
+            self.max_depth = self.max_depth.max(self.depth);
+            self.events.push("{".into());
+            Ok(())
+        }
+
+        fn key(&mut self, key: Cow<'a, str>) -> Result<(), String> {
+            if key == "stop" {
+                return Err("stopped".into());
+            }
+            self.events.push(format!("{}:", key));
+            Ok(())
+        }
+
+        fn end_object(&mut self) -> Result<(), String> {
+            self.depth -= 1;
+            self.events.push("}".into());
+            Ok(())
+        }
+
+        fn start_array(&mut self) -> Result<(), String> {
+            self.events.push("[".into());
+            Ok(())
+        }
+
+        fn end_array(&mut self) -> Result<(), String> {
+            self.events.push("]".into());
+            Ok(())
+        }
+
+        fn number(&mut self, value: Number<'a>) -> Result<(), String> {
+            self.events.push(value.to_string());
+            Ok(())
+        }
+
+        fn bool(&mut self, value: bool) -> Result<(), String> {
+            self.events.push(value.to_string());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_events_in_source_order() {
+        let mut stats = Stats::default();
+        let input = r#"{"a": [1, {"b": true}], "c": "skipped", "a": null}"#;
+        parse_json_events(input, &mut stats).unwrap();
+        assert_eq!(stats.events.join(" "), "{ a: [ 1 { b: true } ] c: a: }");
+        assert_eq!(stats.max_depth, 2);
+    }
+
+    #[test]
+    fn test_handler_can_stop_parsing() {
+        let mut stats = Stats::default();
+        let result = parse_json_events(r#"[{"a": 1}, {"stop": 2}, tru]"#, &mut stats);
+        assert!(matches!(result, Err(HandlerError::Handler(e)) if e == "stopped"));
+        assert_eq!(stats.events.join(" "), "[ { a: 1 } {");
+    }
+}
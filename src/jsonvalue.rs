@@ -1,36 +1,79 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
 use std::fmt;
 
+use crate::number::Number;
+use crate::object::JSONObject;
+use crate::serializer::{SerializerOptions, write_value};
+
+/// A parsed JSON value. Strings and keys borrow from the input where possible, use
+/// `into_owned` to detach the value from the input it was parsed from.
 #[derive(Debug, PartialEq)]
-pub enum JSONValue {
-    Array(Vec<JSONValue>),
+pub enum JSONValue<'a> {
+    Array(Vec<JSONValue<'a>>),
     /// Classic boolean - true, false
     Bool(bool),
     /// Used to represent null value in JSON
     Null,
-    /// floating point number for decimal numbers in JSON
-    Number(f64),
-    Object(HashMap<String, JSONValue>), //TODO: Use something else to preserve order, perhaps use vec with prescanning of capacity
-    String(String),                     // TODO: use &str with lifetime for better performance or Cow<'a, str)
+    /// Integer or floating point number, along with the text it was written as
+    Number(Number<'a>),
+    /// Members are kept in the order they appear in the source
+    Object(JSONObject<'a>),
+    /// Borrowed from the input, unless the string contained escape sequences
+    String(Cow<'a, str>),
+}
+
+impl JSONValue<'_> {
+    /// Copies every borrowed string, so the value no longer refers to the input
+    pub fn into_owned(self) -> JSONValue<'static> {
+        match self {
+            JSONValue::Array(arr) => {
+                JSONValue::Array(arr.into_iter().map(JSONValue::into_owned).collect())
+            }
+            JSONValue::Bool(b) => JSONValue::Bool(b),
+            JSONValue::Null => JSONValue::Null,
+            JSONValue::Number(n) => JSONValue::Number(n.into_owned()),
+            JSONValue::Object(map) => JSONValue::Object(map.into_owned()),
+            JSONValue::String(s) => JSONValue::String(Cow::Owned(s.into_owned())),
+        }
+    }
+}
+
+/// Writes compact JSON, or pretty printed JSON with the alternate flag (`{:#}`)
+impl fmt::Display for JSONValue<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let options = if f.alternate() {
+            SerializerOptions::pretty()
+        } else {
+            SerializerOptions::compact()
+        };
+        write_value(f, self, &options)
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
-pub enum TokenKind {
+pub enum TokenKind<'a> {
     LeftBrace,    // {
     RightBrace,   // }
     LeftBracket,  // [
     RightBracket, // ]
     Colon,        // :
     Comma,        // ,
-    String(String), //TODO: again use Cow<a', str>
-    Number(f64), //TODO: store raw slice; parse later
+    String(Cow<'a, str>),
+    Number(Number<'a>),
     True,
     False,
     Null,
+    /// An unquoted word, only produced in JSON5 mode. Used for keys and the words standing for
+    /// values, `true`, `false`, `null`, `Infinity` and `NaN`.
+    Identifier(Cow<'a, str>),
+    /// The RS control character, only produced when lexing RFC 7464 sequences
+    RecordSeparator,
+    /// A whole `//` or `/* */` comment, only produced by `lexer::Tokens` in trivia mode
+    Comment(Cow<'a, str>),
     EOF,
 }
 
-impl fmt::Display for TokenKind {
+impl fmt::Display for TokenKind<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             TokenKind::LeftBrace => write!(f, "{{"),
@@ -44,26 +87,33 @@ impl fmt::Display for TokenKind {
             TokenKind::True => write!(f, "true"),
             TokenKind::False => write!(f, "false"),
             TokenKind::Null => write!(f, "null"),
+            TokenKind::Identifier(s) => write!(f, "identifier: {}", s),
+            TokenKind::RecordSeparator => write!(f, "record separator"),
+            TokenKind::Comment(_) => write!(f, "comment"),
             TokenKind::EOF => write!(f, "EOF"),
         }
     }
 }
 
-impl TokenKind {
-    pub fn display_len(&self) -> usize {
+impl TokenKind<'_> {
+    /// Detaches the token from the input, used when a token ends up inside an error
+    pub fn into_owned(self) -> TokenKind<'static> {
         match self {
-            TokenKind::LeftBrace
-            | TokenKind::RightBrace
-            | TokenKind::LeftBracket
-            | TokenKind::RightBracket
-            | TokenKind::Colon
-            | TokenKind::Comma => 1,
-            TokenKind::String(s) => s.len() + 2, // include quotes
-            TokenKind::Number(n) => n.to_string().len(),
-            TokenKind::True => 4,
-            TokenKind::False => 5,
-            TokenKind::Null => 4,
-            TokenKind::EOF => 1,
+            TokenKind::LeftBrace => TokenKind::LeftBrace,
+            TokenKind::RightBrace => TokenKind::RightBrace,
+            TokenKind::LeftBracket => TokenKind::LeftBracket,
+            TokenKind::RightBracket => TokenKind::RightBracket,
+            TokenKind::Colon => TokenKind::Colon,
+            TokenKind::Comma => TokenKind::Comma,
+            TokenKind::String(s) => TokenKind::String(Cow::Owned(s.into_owned())),
+            TokenKind::Number(n) => TokenKind::Number(n.into_owned()),
+            TokenKind::True => TokenKind::True,
+            TokenKind::False => TokenKind::False,
+            TokenKind::Null => TokenKind::Null,
+            TokenKind::Identifier(s) => TokenKind::Identifier(Cow::Owned(s.into_owned())),
+            TokenKind::RecordSeparator => TokenKind::RecordSeparator,
+            TokenKind::Comment(s) => TokenKind::Comment(Cow::Owned(s.into_owned())),
+            TokenKind::EOF => TokenKind::EOF,
         }
     }
 }
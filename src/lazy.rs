@@ -0,0 +1,445 @@
+use std::{borrow::Cow, cell::OnceCell};
+
+use crate::{
+    errors::{ParserError, ParserErrorKind},
+    jsonvalue::{JSONValue, TokenKind},
+    lexer::{Lexer, Token},
+    options::{DuplicateKeys, ParserOptions},
+    parser::{Parser, error_at},
+    span::Span,
+    spanned::{array_index, unescape_pointer_token},
+};
+
+/// Skims a document and returns a lazy view of it, see `LazyValue`. The skim lexes every token
+/// and checks that brackets match and do not nest too deeply, wherever in the document they
+/// are. The rest of the grammar is only checked by `parse`.
+pub fn parse_lazy(input: &str) -> Result<LazyValue<'_>, ParserError> {
+    parse_lazy_with(input, &ParserOptions::default())
+}
+
+/// Skims a document using the given options and returns a lazy view of it
+pub fn parse_lazy_with<'a>(
+    input: &'a str,
+    options: &ParserOptions,
+) -> Result<LazyValue<'a>, ParserError> {
+    let raw = |span| RawValue {
+        source: input,
+        span,
+        options: options.clone(),
+    };
+    let mut skim = Skim::new(Lexer::with_options(input, options), options.max_depth);
+    let first = skim.next()?;
+    // The document is skimmed as a whole anyway, so its children are found along the way
+    let children = skim.children(&first, |span| LazyValue::new(raw(span)))?;
+    let span = match children {
+        Children::Scalar => skim.value(&first)?.ok_or_else(|| unexpected(&first))?,
+        _ => first.span.to(skim.close(0)?),
+    };
+    let token = skim.next()?;
+    if token.kind != TokenKind::EOF {
+        let kind = ParserErrorKind::ExpectedToken(TokenKind::EOF, token.kind.clone().into_owned());
+        return Err(error_at(kind, &token));
+    }
+    Ok(LazyValue {
+        raw: raw(span),
+        children: OnceCell::from(children),
+    })
+}
+
+fn unexpected(token: &Token) -> ParserError {
+    error_at(
+        ParserErrorKind::UnexpectedToken(token.kind.clone().into_owned()),
+        token,
+    )
+}
+
+/// Goes through tokens keeping track of nothing but the arrays and objects they open and close
+struct Skim<'a> {
+    lexer: Lexer<'a>,
+    closing: Vec<TokenKind<'static>>, // What closes the arrays and objects we are in, innermost last
+    last: Span,                       // Of the last token read
+    max_depth: usize,
+}
+
+impl<'a> Skim<'a> {
+    fn new(lexer: Lexer<'a>, max_depth: usize) -> Self {
+        Self {
+            lexer,
+            closing: Vec::new(),
+            last: Span::default(),
+            max_depth,
+        }
+    }
+
+    /// Fails on tokens that do not lex, close the wrong bracket or nest too deeply
+    fn next(&mut self) -> Result<Token<'a>, ParserError> {
+        let token = self.lexer.next_token()?;
+        self.last = token.span;
+        match &token.kind {
+            TokenKind::LeftBracket | TokenKind::LeftBrace => {
+                if self.closing.len() >= self.max_depth {
+                    let kind = ParserErrorKind::DepthLimitExceeded(self.max_depth);
+                    return Err(error_at(kind, &token));
+                }
+                self.closing.push(match token.kind {
+                    TokenKind::LeftBracket => TokenKind::RightBracket,
+                    _ => TokenKind::RightBrace,
+                });
+            }
+            TokenKind::RightBracket | TokenKind::RightBrace | TokenKind::EOF => {
+                if let Some(close) = self.closing.pop()
+                    && close != token.kind
+                {
+                    let kind =
+                        ParserErrorKind::ExpectedToken(close, token.kind.clone().into_owned());
+                    return Err(error_at(kind, &token));
+                }
+            }
+            _ => {}
+        }
+        Ok(token)
+    }
+
+    /// Reads on to the end of the value `first` starts, returning its span. None when `first`
+    /// cannot start a value.
+    fn value(&mut self, first: &Token<'a>) -> Result<Option<Span>, ParserError> {
+        let end = match first.kind {
+            TokenKind::LeftBracket | TokenKind::LeftBrace => self.close(self.closing.len() - 1)?,
+            TokenKind::RightBracket
+            | TokenKind::RightBrace
+            | TokenKind::Comma
+            | TokenKind::Colon
+            | TokenKind::EOF => return Ok(None),
+            _ => first.span,
+        };
+        Ok(Some(first.span.to(end)))
+    }
+
+    /// Reads on until only `depth` arrays and objects are open, returning the span of the last
+    /// token read
+    fn close(&mut self, depth: usize) -> Result<Span, ParserError> {
+        while self.closing.len() > depth {
+            self.next()?;
+        }
+        Ok(self.last)
+    }
+
+    /// Finds where the members or elements of the array or object `open` starts lie, without
+    /// looking into them. Stops at its end, or early at the first token out of place, which
+    /// `parse` reports.
+    fn children(
+        &mut self,
+        open: &Token<'a>,
+        child: impl Fn(Span) -> LazyValue<'a>,
+    ) -> Result<Children<'a>, ParserError> {
+        let (mut members, mut elements) = (Vec::new(), Vec::new());
+        let is_object = match open.kind {
+            TokenKind::LeftBrace => true,
+            TokenKind::LeftBracket => false,
+            _ => return Ok(Children::Scalar),
+        };
+        let depth = self.closing.len();
+        loop {
+            let token = self.next()?;
+            if self.closing.len() < depth {
+                break;
+            }
+            if is_object {
+                let key = match token.kind {
+                    TokenKind::String(key) | TokenKind::Identifier(key) => key,
+                    _ => break,
+                };
+                if self.next()?.kind != TokenKind::Colon {
+                    break;
+                }
+                let first = self.next()?;
+                match self.value(&first)? {
+                    Some(span) => members.push((key, child(span))),
+                    None => break,
+                }
+            } else {
+                match self.value(&token)? {
+                    Some(span) => elements.push(child(span)),
+                    None => break,
+                }
+            }
+            if self.next()?.kind != TokenKind::Comma {
+                break;
+            }
+        }
+        Ok(match is_object {
+            true => Children::Object(members),
+            false => Children::Array(elements),
+        })
+    }
+}
+
+/// Where a value lies in a document that has been skimmed, without the value itself. Parse it
+/// when you need it.
+#[derive(Debug, Clone)]
+pub struct RawValue<'a> {
+    source: &'a str,
+    /// The whole value, including the quotes of strings and the brackets of arrays and objects
+    span: Span,
+    options: ParserOptions,
+}
+
+impl<'a> RawValue<'a> {
+    /// The value exactly as written in the input
+    pub fn as_str(&self) -> &'a str {
+        &self.source[self.span.start..self.span.end]
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    /// Parses the value, failing like `parse_json` on whatever the skim does not check. Every
+    /// call parses it again.
+    pub fn parse(&self) -> Result<JSONValue<'a>, ParserError> {
+        Parser::from_tokens(self.lexer()).parse()
+    }
+
+    /// Lexes the value alone, with positions as in the whole input
+    fn lexer(&self) -> Lexer<'a> {
+        let Span {
+            start,
+            start_line,
+            start_column,
+            end,
+            ..
+        } = self.span;
+        Lexer::with_options(&self.source[..end], &self.options).resume_at(
+            start,
+            start_line,
+            start_column,
+        )
+    }
+
+    /// Finds where the members or elements of an array or object lie, see `Skim::children`
+    fn children(&self) -> Result<Children<'a>, ParserError> {
+        let mut skim = Skim::new(self.lexer(), self.options.max_depth);
+        let open = skim.next()?;
+        skim.children(&open, |span| {
+            LazyValue::new(RawValue {
+                span,
+                ..self.clone()
+            })
+        })
+    }
+}
+
+/// A view of a value that looks into arrays and objects only when asked to, for reading a few
+/// values out of a large document.
+///
+/// Looking up a member or element finds where each member or element of the array or object
+/// lies, once, the first time. For the document itself this is done while skimming it. Nothing
+/// is parsed until `parse` is called, which is also the first time the grammar is checked, so
+/// lookups on malformed input can come up short.
+#[derive(Debug)]
+pub struct LazyValue<'a> {
+    raw: RawValue<'a>,
+    children: OnceCell<Children<'a>>,
+}
+
+#[derive(Debug)]
+enum Children<'a> {
+    /// Members in source order, repeated keys included
+    Object(Vec<(Cow<'a, str>, LazyValue<'a>)>),
+    Array(Vec<LazyValue<'a>>),
+    Scalar,
+}
+
+impl<'a> LazyValue<'a> {
+    fn new(raw: RawValue<'a>) -> Self {
+        Self {
+            raw,
+            children: OnceCell::new(),
+        }
+    }
+
+    pub fn raw(&self) -> &RawValue<'a> {
+        &self.raw
+    }
+
+    /// Parses the whole value, see `RawValue::parse`
+    pub fn parse(&self) -> Result<JSONValue<'a>, ParserError> {
+        self.raw.parse()
+    }
+
+    /// Member of an object by key. Of repeated keys this is the member `parse_json` keeps, the
+    /// first with `DuplicateKeys::FirstWins` and the last otherwise, `members` has them all.
+    /// With `DuplicateKeys::CollectAll` it is the last too, where `parse_json` gathers them all
+    /// into an array, which has no place in the input to point to.
+    pub fn get(&self, key: &str) -> Option<&LazyValue<'a>> {
+        let mut found = self.members().filter(|(k, _)| *k == key).map(|(_, v)| v);
+        match self.raw.options.duplicate_keys {
+            DuplicateKeys::FirstWins => found.next(),
+            _ => found.last(),
+        }
+    }
+
+    /// Element of an array by index
+    pub fn index(&self, index: usize) -> Option<&LazyValue<'a>> {
+        match self.children() {
+            Children::Array(elements) => elements.get(index),
+            _ => None,
+        }
+    }
+
+    /// Looks up a value by JSON Pointer (RFC 6901), such as `/servers/0/port`, looking into
+    /// only the arrays and objects along the way. Repeated keys resolve like in `get`.
+    pub fn pointer(&self, pointer: &str) -> Option<&LazyValue<'a>> {
+        if pointer.is_empty() {
+            return Some(self);
+        }
+        let mut current = self;
+        for token in pointer.strip_prefix('/')?.split('/') {
+            let token = unescape_pointer_token(token);
+            current = match current.children() {
+                Children::Object(_) => current.get(&token)?,
+                Children::Array(elements) => elements.get(array_index(&token)?)?,
+                Children::Scalar => return None,
+            };
+        }
+        Some(current)
+    }
+
+    /// Members of an object in source order, empty for anything else
+    pub fn members(&self) -> impl Iterator<Item = (&str, &LazyValue<'a>)> {
+        let members = match self.children() {
+            Children::Object(members) => members.as_slice(),
+            _ => &[],
+        };
+        members.iter().map(|(key, value)| (key.as_ref(), value))
+    }
+
+    /// Elements of an array, empty for anything else
+    pub fn elements(&self) -> impl Iterator<Item = &LazyValue<'a>> {
+        let elements = match self.children() {
+            Children::Array(elements) => elements.as_slice(),
+            _ => &[],
+        };
+        elements.iter()
+    }
+
+    pub fn is_object(&self) -> bool {
+        matches!(self.children(), Children::Object(_))
+    }
+
+    pub fn is_array(&self) -> bool {
+        matches!(self.children(), Children::Array(_))
+    }
+
+    fn children(&self) -> &Children<'a> {
+        self.children.get_or_init(|| {
+            self.raw
+                .children()
+                .expect("parse_lazy has lexed the whole document and matched its brackets")
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_json;
+
+    const INPUT: &str = r#"{
+  "route": {"service": "users", "port": 8080},
+  "payload": {"items": [1, {"deep": "é\n"}, [true, null]], "size": 2.5e3},
+  "port": 1
+}"#;
+
+    #[test]
+    fn test_lookups() {
+        let doc = parse_lazy(INPUT).unwrap();
+        assert!(doc.is_object());
+        assert_eq!(
+            doc.pointer("/route/service").unwrap().parse().unwrap(),
+            JSONValue::String("users".into())
+        );
+        let items = doc.get("payload").unwrap().get("items").unwrap();
+        assert!(items.is_array());
+        assert_eq!(
+            items.raw().as_str(),
+            r#"[1, {"deep": "é\n"}, [true, null]]"#
+        );
+        assert_eq!(items.elements().count(), 3);
+        assert_eq!(
+            items.index(1).unwrap().parse().unwrap().to_string(),
+            r#"{"deep":"é\n"}"#
+        );
+        assert!(items.index(3).is_none());
+        assert_eq!(
+            doc.pointer("/payload/items/2/0").unwrap().raw().as_str(),
+            "true"
+        );
+        assert!(doc.pointer("/payload/items/01").is_none());
+        assert!(doc.pointer("/route/port/x").is_none());
+        assert_eq!(
+            doc.members().map(|(key, _)| key).collect::<Vec<_>>(),
+            ["route", "payload", "port"]
+        );
+        assert_eq!(doc.parse().unwrap(), parse_json(INPUT).unwrap());
+
+        // Spans point into the whole input
+        let deep = doc.pointer("/payload/items/1/deep").unwrap().raw().span();
+        assert_eq!((deep.start_line, deep.start_column), (3, 37));
+        assert_eq!(&INPUT[deep.start..deep.end], r#""é\n""#);
+    }
+
+    #[test]
+    fn test_only_looks_into_what_is_accessed() {
+        let doc = parse_lazy(INPUT).unwrap();
+        // The document's own members were found while skimming it
+        assert!(doc.children.get().is_some());
+        doc.get("route").unwrap();
+        let payload = doc.get("payload").unwrap();
+        assert!(payload.children.get().is_none());
+        assert!(doc.get("route").unwrap().children.get().is_none());
+    }
+
+    #[test]
+    fn test_repeated_keys() {
+        let input = r#"{"a": 1, "b": 2, "a": 3}"#;
+        let doc = parse_lazy(input).unwrap();
+        assert_eq!(doc.get("a").unwrap().raw().as_str(), "3");
+        assert_eq!(doc.members().count(), 3);
+        let options = ParserOptions::new().duplicate_keys(DuplicateKeys::FirstWins);
+        let doc = parse_lazy_with(input, &options).unwrap();
+        assert_eq!(doc.get("a").unwrap().raw().as_str(), "1");
+
+        // Collected values have no place in the input, the last one is looked up instead
+        let input = r#"{"k": "v", "k": "wt"}"#;
+        let options = ParserOptions::new().duplicate_keys(DuplicateKeys::CollectAll);
+        let doc = parse_lazy_with(input, &options).unwrap();
+        assert_eq!(doc.get("k").unwrap().raw().as_str(), r#""wt""#);
+        assert!(doc.pointer("/k/0").is_none());
+        assert_eq!(doc.parse().unwrap().to_string(), r#"{"k":["v","wt"]}"#);
+    }
+
+    #[test]
+    fn test_skim_checks_tokens_and_brackets() {
+        let options = ParserOptions::new().max_depth(3);
+        let inputs = [
+            r#"{"a": 1, "b": {"c": tru}}"#,
+            r#"{"a": 1, "b": ["c"}}"#,
+            r#"{"a": [1, 2"#,
+            "[[[[1]]]]",
+            "[1] 2",
+            "",
+        ];
+        for input in inputs {
+            assert!(parse_lazy_with(input, &options).is_err(), "{}", input);
+        }
+
+        // The rest is left for parse, lookups stop at what is out of place
+        let doc = parse_lazy(r#"{"a": 1, "b": [1, 2,, 3], "c" 2, "d": 4}"#).unwrap();
+        assert_eq!(doc.members().count(), 2);
+        let b = doc.get("b").unwrap();
+        assert_eq!(b.elements().count(), 2);
+        assert_eq!(b.parse().unwrap_err().code(), "unexpected_token");
+        assert_eq!(doc.parse().unwrap_err(), b.parse().unwrap_err());
+    }
+}
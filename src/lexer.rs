@@ -1,28 +1,94 @@
-use std::{char, str::Chars};
-//TODO: Rewrite lexer to support string slices, instead of taking copies everywhere
+use std::{borrow::Cow, char};
+
 use crate::{
     errors::{LexerError, LexerErrorKinds},
     jsonvalue::TokenKind,
+    number::Number,
+    options::ParserOptions,
+    span::Span,
 };
 
+/// Starts each record of an RFC 7464 JSON text sequence
+const RECORD_SEPARATOR: u8 = 0x1E;
+
 #[derive(Debug, Clone)]
-pub struct Token {
-    pub kind: TokenKind,
+pub struct Token<'a> {
+    pub kind: TokenKind<'a>,
     pub line: usize,
     pub column: usize,
+    /// The whole lexeme, including the quotes of strings
+    pub span: Span,
 }
 
+/// Scans the input as bytes. Everything outside of strings has to be ASCII, so only string
+/// contents can hold multi byte characters, and those are sliced straight out of the input.
 pub(crate) struct Lexer<'a> {
-    // Define the fields for the Lexer struct
-    // e.g., input string, current position, etc.
-    input: std::iter::Peekable<Chars<'a>>,
-    line: usize,   // Current line Number
-    column: usize, // Current column number
+    source: &'a str,
+    bytes: &'a [u8], // source.as_bytes(), kept around to avoid the call in hot loops
+    pos: usize,      // Byte offset of the next byte in source
+    line: usize,     // Current line Number
+    column: usize,   // Current column number, counted in chars
+    token_start: (usize, usize, usize), // pos, line and column where the current token began
     finished: bool,
+    trivia: bool,            // Emit comments as tokens rather than skipping them
+    record_separators: bool, // Emit RS (0x1E) as a token, for RFC 7464 sequences
+    options: ParserOptions,
+}
+
+/// The tokens of a document, for tools that work on tokens rather than values. With
+/// `ParserOptions::comments` set, comments can be kept as `TokenKind::Comment` tokens too.
+pub struct Tokens<'a> {
+    lexer: Lexer<'a>,
+}
+
+impl<'a> Tokens<'a> {
+    pub fn new(input: &'a str, options: &ParserOptions) -> Self {
+        Self {
+            lexer: Lexer::with_options(input, options),
+        }
+    }
+
+    /// Emit comments as tokens instead of skipping them, like formatters that have to keep
+    /// comments in place need. Only has an effect when comments are enabled.
+    pub fn trivia(mut self, trivia: bool) -> Self {
+        self.lexer.trivia = trivia;
+        self
+    }
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Result<Token<'a>, LexerError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.lexer.next()
+    }
+}
+
+/// Where `Parser::from_tokens` and `PullParser` get their tokens from. They parse with the
+/// options the tokens were lexed with, so lexer and parser can never disagree on them.
+pub trait TokenSource<'a>: Iterator<Item = Result<Token<'a>, LexerError>> {
+    fn options(&self) -> &ParserOptions;
+}
+
+impl<'a> TokenSource<'a> for Tokens<'a> {
+    fn options(&self) -> &ParserOptions {
+        &self.lexer.options
+    }
+}
+
+impl<'a> TokenSource<'a> for Lexer<'a> {
+    fn options(&self) -> &ParserOptions {
+        &self.options
+    }
+}
+
+impl<'a, T: TokenSource<'a>> TokenSource<'a> for &mut T {
+    fn options(&self) -> &ParserOptions {
+        (**self).options()
+    }
 }
 
 impl<'a> Iterator for Lexer<'a> {
-    type Item = Result<Token, LexerError>;
+    type Item = Result<Token<'a>, LexerError>;
     fn next(&mut self) -> Option<Self::Item> {
         if self.finished {
             return None;
@@ -37,345 +103,802 @@ impl<'a> Iterator for Lexer<'a> {
                     Some(Ok(token))
                 }
             }
-            Err(e) => Some(Err(e)),
+            Err(e) => {
+                self.skip_bad_lexeme();
+                Some(Err(e))
+            }
         }
     }
 }
 
 impl<'a> Lexer<'a> {
     pub fn new(input: &'a str) -> Self {
-        let chars = input.chars().peekable(); //TODO: we can iterate over bytes instead of chars
+        Self::with_options(input, &ParserOptions::default())
+    }
 
+    pub fn with_options(input: &'a str, options: &ParserOptions) -> Self {
         Self {
-            input: chars,
+            source: input,
+            bytes: input.as_bytes(),
+            pos: 0,
             line: 1,
             column: 1,
+            token_start: (0, 1, 1),
             finished: false,
+            trivia: false,
+            record_separators: false,
+            options: options.clone(),
         }
     }
 
-    pub fn next_token(&mut self) -> Result<Token, LexerError> {
+    /// Lex the RS control character that starts every record of an RFC 7464 JSON text sequence
+    /// as a `TokenKind::RecordSeparator`. Strings end at one, as records may be cut short.
+    pub fn record_separators(mut self, record_separators: bool) -> Self {
+        self.record_separators = record_separators;
+        self
+    }
+
+    /// Carries on at byte `pos` of the input, which lies on the given line and column. Used to
+    /// lex a window of a larger input, see `reader::ReadLexer`.
+    pub(crate) fn resume_at(mut self, pos: usize, line: usize, column: usize) -> Self {
+        self.pos = pos;
+        self.line = line;
+        self.column = column;
+        self
+    }
+
+    /// Byte offset, line and column of the next byte
+    pub(crate) fn position(&self) -> (usize, usize, usize) {
+        (self.pos, self.line, self.column)
+    }
+
+    pub fn next_token(&mut self) -> Result<Token<'a>, LexerError> {
         // Start by skipping whitespace
-        self.skip_whitespace();
-        let next_char = self.peek();
-        let c = match next_char {
-            Some(c) => c,
+        self.skip_whitespace()?;
+        self.token_start = (self.pos, self.line, self.column);
+        let b = match self.peek() {
+            Some(b) => b,
             None => {
                 return Ok(self.new_token(TokenKind::EOF));
             }
         };
-        match c {
-            '{' => Ok(self.new_token_advance(TokenKind::LeftBrace)),
-            '}' => Ok(self.new_token_advance(TokenKind::RightBrace)),
-            '[' => Ok(self.new_token_advance(TokenKind::LeftBracket)),
-            ']' => Ok(self.new_token_advance(TokenKind::RightBracket)),
-            ':' => Ok(self.new_token_advance(TokenKind::Colon)),
-            ',' => Ok(self.new_token_advance(TokenKind::Comma)),
-            '"' => {
-                let start_column = self.column;
-                let string_value = self.read_string()?;
-                Ok(Token {
-                    kind: TokenKind::String(string_value),
-                    line: self.line,
-                    column: start_column,
-                })
-            }
-            n if n.is_ascii_digit() || n == '-' => {
+        match b {
+            b'{' => Ok(self.new_token_advance(TokenKind::LeftBrace)),
+            b'}' => Ok(self.new_token_advance(TokenKind::RightBrace)),
+            b'[' => Ok(self.new_token_advance(TokenKind::LeftBracket)),
+            b']' => Ok(self.new_token_advance(TokenKind::RightBracket)),
+            b':' => Ok(self.new_token_advance(TokenKind::Colon)),
+            b',' => Ok(self.new_token_advance(TokenKind::Comma)),
+            b'"' => {
+                let string_value = self.read_string(b'"')?;
+                Ok(self.new_token(TokenKind::String(string_value)))
+            }
+            n if n.is_ascii_digit() || n == b'-' => {
                 let number_value = self.read_number()?;
                 Ok(self.new_token(TokenKind::Number(number_value)))
             }
 
-            n @ ('f' | 't' | 'n') => {
+            // JSON5 has no literals of its own, every word is an identifier, see read_identifier
+            n @ (b'f' | b't' | b'n') if !self.options.json5 => {
                 let token_kind = self.read_literal(n)?;
                 Ok(self.new_token(token_kind))
             }
-            c => {
+            b'I' | b'N' if self.options.allows_nan_and_infinity() && !self.options.json5 => {
+                let number_value = self.read_number()?;
+                Ok(self.new_token(TokenKind::Number(number_value)))
+            }
+            b'\'' if self.options.allows_single_quotes() => {
+                let string_value = self.read_string(b'\'')?;
+                Ok(self.new_token(TokenKind::String(string_value)))
+            }
+            b'+' | b'.' if self.options.json5 => self.next_json5_token(b),
+            _ if self.options.json5 && self.current_char().is_some_and(is_identifier_start) => {
+                self.next_json5_token(b)
+            }
+            RECORD_SEPARATOR if self.record_separators => {
+                Ok(self.new_token_advance(TokenKind::RecordSeparator))
+            }
+            // Only reached in trivia mode, otherwise comments count as whitespace
+            b'/' if self.options.allows_comments() => {
+                self.skip_comment()?;
+                let text = &self.source[self.token_start.0..self.pos];
+                Ok(self.new_token(TokenKind::Comment(Cow::Borrowed(text))))
+            }
+            _ => {
+                // Might be the first byte of a multi byte char, report and skip the whole char
+                let c = self.current_char().unwrap();
                 let err = self.return_error(LexerErrorKinds::UnexcpectedChar(c));
-                self.advance(); // skip it!
+                self.advance_by(c.len_utf8()); // skip it!
                 Err(err)
             }
         }
     }
 
+    /// Tokens that only exist in JSON5: numbers starting with `+` or `.`, and identifiers
+    fn next_json5_token(&mut self, first: u8) -> Result<Token<'a>, LexerError> {
+        let kind = match first {
+            b'+' | b'.' => TokenKind::Number(self.read_number()?),
+            _ => TokenKind::Identifier(self.read_identifier()),
+        };
+        Ok(self.new_token(kind))
+    }
+
     /// Generates a simple token, that is those of one char. Do not use on other kinds, as the
     /// function advances
-    fn new_token_advance(&mut self, kind: TokenKind) -> Token {
-        let token = Token {
-            kind,
-            line: self.line,
-            column: self.column,
-        };
+    fn new_token_advance(&mut self, kind: TokenKind<'a>) -> Token<'a> {
         self.advance();
-        token
+        self.new_token(kind)
     }
 
-    fn new_token(&mut self, kind: TokenKind) -> Token {
+    /// Builds a token spanning from where the current token began to the current position, so
+    /// call it once the lexeme has been consumed
+    fn new_token(&self, kind: TokenKind<'a>) -> Token<'a> {
+        let span = self.token_span();
         Token {
             kind,
-            line: self.line,
-            column: self.column,
+            line: span.start_line,
+            column: span.start_column,
+            span,
+        }
+    }
+
+    fn token_span(&self) -> Span {
+        let (start, start_line, start_column) = self.token_start;
+        Span {
+            start,
+            end: self.pos,
+            start_line,
+            start_column,
+            end_line: self.line,
+            end_column: self.column,
+        }
+    }
+
+    /// Moves past whatever is left of a lexeme that failed to lex, so the next token starts
+    /// after it rather than in the middle of it
+    fn skip_bad_lexeme(&mut self) {
+        match self.bytes[self.token_start.0] {
+            b'"' => self.skip_string(b'"'),
+            b'\'' if self.options.allows_single_quotes() => self.skip_string(b'\''),
+            b'-' | b'0'..=b'9' | b't' | b'f' | b'n' => self.skip_word(),
+            b'+' | b'.' if self.options.json5 => self.skip_word(),
+            b'I' | b'N' if self.options.allows_nan_and_infinity() => self.skip_word(),
+            // A single unexpected char, which has already been skipped
+            _ => {}
         }
     }
 
-    //TODO: possible mark advance, peek and skip_whitespace with [inline(always)]
+    /// Skips the rest of a broken string, up to the closing quote or the end of the line, as
+    /// strings never span lines
+    fn skip_string(&mut self, quote: u8) {
+        while let Some(b) = self.peek() {
+            match b {
+                b if b == quote => {
+                    self.advance();
+                    break;
+                }
+                b'\n' | b'\r' => break,
+                RECORD_SEPARATOR if self.record_separators => break,
+                b'\\' => {
+                    self.advance();
+                    if let Some(c) = self.current_char()
+                        && c != '\n'
+                        && c != '\r'
+                    {
+                        self.advance_by(c.len_utf8());
+                    }
+                }
+                _ => {
+                    let c = self.current_char().unwrap();
+                    self.advance_by(c.len_utf8());
+                }
+            }
+        }
+    }
+
+    /// Skips the rest of a broken number or literal
+    fn skip_word(&mut self) {
+        while let Some(b'0'..=b'9' | b'a'..=b'z' | b'A'..=b'Z' | b'.' | b'+' | b'-') = self.peek() {
+            self.advance();
+        }
+    }
+
+    /// Moves past a single ASCII byte
+    #[inline(always)]
     fn advance(&mut self) {
-        match self.input.next() {
-            Some('\n') => {
+        match self.bytes.get(self.pos) {
+            Some(b'\n') => {
+                self.pos += 1;
                 self.line += 1;
                 self.column = 1; // Reset column on new line
             }
-            Some(_) => self.column += 1,
+            Some(_) => {
+                self.pos += 1;
+                self.column += 1;
+            }
             None => {} // End of input
         }
     }
 
-    fn peek(&mut self) -> Option<char> {
-        self.input.peek().copied() //TODO: dont copy
+    /// Moves past `len` bytes that contain no newlines, counting the chars they make up
+    #[inline(always)]
+    fn advance_by(&mut self, len: usize) {
+        let end = self.pos + len;
+        self.column += char_count(&self.bytes[self.pos..end]);
+        self.pos = end;
+    }
+
+    #[inline(always)]
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    /// Decodes the char starting at the current position, used where errors need the full char
+    fn current_char(&self) -> Option<char> {
+        self.source[self.pos..].chars().next()
     }
 
-    fn skip_whitespace(&mut self) {
-        while let Some(c) = self.peek() {
-            match c {
-                ' ' | '\n' | '\r' | '\t' => self.advance(),
+    /// Also skips comments when they are enabled, unless they are wanted as tokens
+    #[inline(always)]
+    fn skip_whitespace(&mut self) -> Result<(), LexerError> {
+        while let Some(b) = self.peek() {
+            match b {
+                b' ' | b'\n' | b'\r' | b'\t' => self.advance(),
+                b'/' if self.options.allows_comments() && !self.trivia => self.skip_comment()?,
+                // Vertical tab and form feed
+                0x0B | 0x0C if self.options.json5 => self.advance(),
+                0x80.. if self.options.json5 => match self.current_char() {
+                    Some(c) if is_json5_space(c) => self.advance_by(c.len_utf8()),
+                    _ => break,
+                },
                 _ => break,
             }
         }
+        Ok(())
     }
 
-    fn read_string(&mut self) -> Result<String, LexerError> {
-        //TODO: Pre allocate size, and use Cow<_,_> instead
-        // When we find escape characters create and copy content to String, else return slice
-        let mut result = String::with_capacity(8);
-        self.advance(); // Skip the opening quote
-
-        while let Some(c) = self.peek() {
-            if c == '"' {
-                self.advance();
-                return Ok(result);
-            }
-            if c == '\n' || c == '\r' {
-                return Err(self.return_error(LexerErrorKinds::UnclosedString));
+    /// Moves past a `//` comment, which runs to the end of the line, or a `/* */` comment
+    #[inline(never)]
+    fn skip_comment(&mut self) -> Result<(), LexerError> {
+        self.token_start = (self.pos, self.line, self.column);
+        match self.bytes.get(self.pos + 1) {
+            Some(b'/') => {
+                // The newline is left for skip_whitespace
+                let len = self.bytes[self.pos..]
+                    .iter()
+                    .position(|&b| b == b'\n' || b == b'\r')
+                    .unwrap_or(self.bytes.len() - self.pos);
+                self.advance_by(len);
             }
-            if c < '\u{20}' {
-                return Err(self.return_error(LexerErrorKinds::UnescapedControlCharacter));
+            Some(b'*') => {
+                self.advance_by(2);
+                loop {
+                    let run = self.bytes[self.pos..]
+                        .iter()
+                        .position(|&b| b == b'*' || b == b'\n')
+                        .unwrap_or(self.bytes.len() - self.pos);
+                    self.advance_by(run);
+                    match self.peek() {
+                        Some(b'*') if self.bytes.get(self.pos + 1) == Some(&b'/') => {
+                            self.advance_by(2);
+                            break;
+                        }
+                        Some(_) => self.advance(),
+                        None => return Err(self.return_error(LexerErrorKinds::UnterminatedComment)),
+                    }
+                }
             }
-            if c == '\\' {
-                self.read_escape_sequence(&mut result)?;
-            } else {
-                result.push(c);
+            // A lone slash is as unexpected as without comments
+            _ => {
+                let err = self.return_error(LexerErrorKinds::UnexcpectedChar('/'));
                 self.advance();
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a string in `quote`s, which are always double quotes outside of JSON5
+    #[inline(always)]
+    fn read_string(&mut self, quote: u8) -> Result<Cow<'a, str>, LexerError> {
+        self.advance(); // Skip the opening quote
+        let start = self.pos;
+        // Strings without escapes are returned as a slice of the input. We only copy into an
+        // owned String once the first escape shows up.
+        let mut owned: Option<String> = None;
+
+        loop {
+            // Skip over the run of bytes that need no special handling in one go
+            let run_start = self.pos;
+            let run_len = self.bytes[run_start..]
+                .iter()
+                .position(|&b| b == quote || b == b'\\' || b < 0x20)
+                .unwrap_or(self.bytes.len() - run_start);
+            self.advance_by(run_len);
+            if let Some(result) = owned.as_mut() {
+                // Runs start and end next to ASCII bytes, so they always lie on char boundaries
+                result.push_str(&self.source[run_start..self.pos]);
+            }
+
+            match self.peek() {
+                Some(b) if b == quote => {
+                    let end = self.pos;
+                    self.advance();
+                    return Ok(match owned {
+                        Some(result) => Cow::Owned(result),
+                        None => Cow::Borrowed(&self.source[start..end]),
+                    });
+                }
+                Some(b'\\') => {
+                    let result = owned.get_or_insert_with(|| {
+                        let mut result = String::with_capacity(self.pos - start + 8);
+                        result.push_str(&self.source[start..self.pos]);
+                        result
+                    });
+                    self.read_escape_sequence(result)?;
+                }
+                Some(b'\n' | b'\r') => {
+                    return Err(self.return_error(LexerErrorKinds::UnclosedString));
+                }
+                Some(RECORD_SEPARATOR) if self.record_separators => {
+                    return Err(self.return_error(LexerErrorKinds::UnclosedString));
+                }
+                // Line breaks still end the string, a missing quote is the likelier mistake
+                Some(b) if b < 0x20 && self.options.allows_control_characters() => {
+                    self.advance();
+                    if let Some(result) = owned.as_mut() {
+                        result.push(b as char);
+                    }
+                }
+                Some(_) => {
+                    return Err(self.return_error(LexerErrorKinds::UnescapedControlCharacter));
+                }
+                None => return Err(self.return_error(LexerErrorKinds::UnclosedString)),
             }
         }
-        Err(self.return_error(LexerErrorKinds::UnclosedString))
     }
 
-    fn read_number(&mut self) -> Result<f64, LexerError> {
-        //TODO: Make numbers int or f64, and lazily parse them
-        // possibly only parse when data is needed ie even after parsing
-        let mut nums = String::with_capacity(8);
+    fn read_number(&mut self) -> Result<Number<'a>, LexerError> {
+        if self.options.json5 {
+            return self.read_json5_number();
+        }
+        let start = self.pos;
 
         // Check for optional minus
-        if let Some('-') = self.peek() {
-            nums.push('-');
+        if let Some(b'-') = self.peek() {
             self.advance(); // consume it
         }
-
-        //TODO: after using slices, we no longer need to branch out
+        if self.options.allows_nan_and_infinity() && matches!(self.peek(), Some(b'I' | b'N')) {
+            return self.read_nan_or_infinity(start);
+        }
 
         // Read integers
-        self.read_integer(&mut nums)?;
+        self.read_integer()?;
 
         // Optional fraction
-        self.read_fraction(&mut nums)?;
+        let has_fraction = self.read_fraction()?;
 
         // Optional exponent
-        self.read_exponent(&mut nums)?;
+        let has_exponent = self.read_exponent()?;
+
+        let raw = Cow::Borrowed(&self.source[start..self.pos]);
+        // Leading zeros would be written back out in arbitrary precision form
+        let arbitrary_precision = self.options.arbitrary_precision
+            && !(self.options.leading_zeros && has_leading_zero(&raw));
+        Number::from_lexeme(raw, !has_fraction && !has_exponent, arbitrary_precision)
+            .ok_or_else(|| self.return_error(LexerErrorKinds::CastingError))
+    }
+
+    /// Reads `Infinity` or `NaN`, which starts at the current position, after the sign if the
+    /// number has one
+    fn read_nan_or_infinity(&mut self, start: usize) -> Result<Number<'a>, LexerError> {
+        let word: &[u8] = if self.peek() == Some(b'I') {
+            b"Infinity"
+        } else {
+            b"NaN"
+        };
+        for &expected in word {
+            match self.peek() {
+                Some(b) if b == expected => self.advance(),
+                _ => return Err(self.return_error(LexerErrorKinds::InvalidLiteral)),
+            }
+        }
+        if self.peek().is_some_and(|b| b.is_ascii_alphanumeric()) {
+            return Err(self.return_error(LexerErrorKinds::InvalidLiteral));
+        }
+        let raw = Cow::Borrowed(&self.source[start..self.pos]);
+        Number::from_lexeme(raw, false, false)
+            .ok_or_else(|| self.return_error(LexerErrorKinds::CastingError))
+    }
+
+    /// On top of JSON numbers JSON5 allows a `+` sign, hexadecimal integers, a decimal point
+    /// without digits on one side, and signed `Infinity` and `NaN`. Unsigned ones are read as
+    /// identifiers.
+    fn read_json5_number(&mut self) -> Result<Number<'a>, LexerError> {
+        let start = self.pos;
+        let sign = match self.peek() {
+            Some(b @ (b'-' | b'+')) => {
+                self.advance();
+                Some(b)
+            }
+            _ => None,
+        };
+
+        match (self.peek(), self.bytes.get(self.pos + 1)) {
+            (Some(b'I' | b'N'), _) if self.options.allows_nan_and_infinity() => {
+                return self.read_nan_or_infinity(start);
+            }
+            (Some(b'0'), Some(b'x' | b'X')) => {
+                self.advance_by(2);
+                let digits_start = self.pos;
+                while self.peek().is_some_and(|b| b.is_ascii_hexdigit()) {
+                    self.advance();
+                }
+                if self.pos == digits_start {
+                    return Err(self.return_error(LexerErrorKinds::InvalidNumber));
+                }
+                let raw = Cow::Borrowed(&self.source[start..self.pos]);
+                let digits = &self.source[digits_start..self.pos];
+                return Ok(Number::from_hex_lexeme(raw, sign == Some(b'-'), digits));
+            }
+            _ => {}
+        }
+
+        let has_integer = self.peek() != Some(b'.');
+        if has_integer {
+            self.read_integer()?;
+        }
+        let has_fraction = if let Some(b'.') = self.peek() {
+            self.advance();
+            // One side of the point needs digits, `.` alone is not a number
+            if self.skip_digits() == 0 && !has_integer {
+                return Err(self.return_error(LexerErrorKinds::InvalidDecimal));
+            }
+            true
+        } else {
+            false
+        };
+        let has_exponent = self.read_exponent()?;
 
-        nums.parse::<f64>()
-            .map_err(|_| self.return_error(LexerErrorKinds::CastingError))
+        let raw = Cow::Borrowed(&self.source[start..self.pos]);
+        // Only numbers written as valid JSON are kept as text, others would be written back
+        // out as they are
+        let is_json = sign != Some(b'+')
+            && has_integer
+            && !has_leading_zero(&raw)
+            && !raw.contains(".e")
+            && !raw.contains(".E")
+            && !raw.ends_with('.');
+        Number::from_lexeme(
+            raw,
+            !has_fraction && !has_exponent,
+            self.options.arbitrary_precision && is_json,
+        )
+        .ok_or_else(|| self.return_error(LexerErrorKinds::CastingError))
     }
 
-    fn read_integer(&mut self, nums: &mut String) -> Result<(), LexerError> {
+    /// Reads a JSON5 identifier, an unquoted key or a word such as `true` or `Infinity`.
+    /// Escape sequences in identifiers are not supported.
+    fn read_identifier(&mut self) -> Cow<'a, str> {
+        let start = self.pos;
+        while let Some(c) = self.current_char()
+            && is_identifier_part(c)
+        {
+            self.advance_by(c.len_utf8());
+        }
+        Cow::Borrowed(&self.source[start..self.pos])
+    }
+
+    /// Consumes a run of ASCII digits, returning how many there were
+    fn skip_digits(&mut self) -> usize {
+        let count = self.bytes[self.pos..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count();
+        self.pos += count;
+        self.column += count;
+        count
+    }
+
+    fn read_integer(&mut self) -> Result<(), LexerError> {
         // Read integers
         match self.peek() {
-            Some('0') => {
-                nums.push('0');
+            Some(b'0') => {
                 self.advance();
                 // 0 cannot be followed by other digits
-                if let Some(c) = self.peek() {
-                    if c.is_ascii_digit() {
+                if let Some(c) = self.peek()
+                    && c.is_ascii_digit()
+                {
+                    if !self.options.leading_zeros {
                         return Err(self.return_error(LexerErrorKinds::LeadingZero));
                     }
+                    self.skip_digits();
                 }
             }
             Some(c) if c.is_ascii_digit() => {
-                while let Some(d) = self.peek() {
-                    if d.is_ascii_digit() {
-                        nums.push(d);
-                        self.advance()
-                    } else {
-                        break;
-                    }
-                }
+                self.skip_digits();
             }
             _ => return Err(self.return_error(LexerErrorKinds::InvalidNumber)),
         }
         Ok(())
     }
 
-    fn read_fraction(&mut self, nums: &mut String) -> Result<(), LexerError> {
-        if let Some('.') = self.peek() {
-            let mut num_found = false;
-            nums.push('.');
+    /// Returns whether there was a fraction
+    fn read_fraction(&mut self) -> Result<bool, LexerError> {
+        if let Some(b'.') = self.peek() {
             self.advance();
-
-            while let Some(d) = self.peek() {
-                // We continue appending numeric values
-                if d.is_ascii_digit() {
-                    nums.push(d);
-                    self.advance();
-                    num_found = true;
-                } else {
-                    break;
-                }
-            }
-
-            if !num_found {
+            if self.skip_digits() == 0 {
                 return Err(self.return_error(LexerErrorKinds::InvalidDecimal));
             }
+            return Ok(true);
         }
-        Ok(())
+        Ok(false)
     }
 
-    fn read_exponent(&mut self, nums: &mut String) -> Result<(), LexerError> {
-        if let Some('e') | Some('E') = self.peek() {
-            nums.push('e');
+    /// Returns whether there was an exponent
+    fn read_exponent(&mut self) -> Result<bool, LexerError> {
+        if let Some(b'e' | b'E') = self.peek() {
             self.advance();
 
             // Exponent can be followed by optional sign + or -
-            if let Some(sign @ ('+' | '-')) = self.peek() {
-                nums.push(sign);
+            if let Some(b'+' | b'-') = self.peek() {
                 self.advance();
             }
 
             // If we have an exponent, atleast a digit must follow
-            let mut digit_found = false;
-            while let Some(d) = self.peek() {
-                if d.is_ascii_digit() {
-                    nums.push(d);
-                    self.advance();
-                    digit_found = true;
-                } else {
-                    break;
-                }
-            }
-
-            if !digit_found {
+            if self.skip_digits() == 0 {
                 return Err(self.return_error(LexerErrorKinds::InvalidExponent));
             }
+            return Ok(true);
         }
-        Ok(())
+        Ok(false)
     }
 
+    /// The error points at the current position, its span covers the token read so far plus
+    /// the offending char
     fn return_error(&self, kind: LexerErrorKinds) -> LexerError {
+        let mut span = self.token_span();
+        if let Some(c) = self.current_char()
+            && c != '\n'
+            && c != '\r'
+        {
+            span.end += c.len_utf8();
+            span.end_column += 1;
+        }
         LexerError {
             kind,
             line: self.line,
             column: self.column,
+            span,
         }
     }
 
-    fn read_literal(&mut self, b_char: char) -> Result<TokenKind, LexerError> {
-        let literal = match b_char {
-            't' => "true",
-            'f' => "false",
-            'n' => "null",
+    fn read_literal(&mut self, first: u8) -> Result<TokenKind<'a>, LexerError> {
+        let literal: &[u8] = match first {
+            b't' => b"true",
+            b'f' => b"false",
+            b'n' => b"null",
             _ => unreachable!(),
         };
-        for expected_char in literal.chars() {
+        for &expected in literal {
             match self.peek() {
-                Some(c) if c == expected_char => self.advance(),
+                Some(b) if b == expected => self.advance(),
                 _ => return Err(self.return_error(LexerErrorKinds::InvalidLiteral)),
             }
         }
-        if self.peek().is_some_and(|c| c.is_ascii_alphanumeric()) {
+        if self.peek().is_some_and(|b| b.is_ascii_alphanumeric()) {
             return Err(self.return_error(LexerErrorKinds::InvalidLiteral));
         }
-        Ok(match b_char {
-            't' => TokenKind::True,
-            'f' => TokenKind::False,
-            'n' => TokenKind::Null,
+        Ok(match first {
+            b't' => TokenKind::True,
+            b'f' => TokenKind::False,
+            b'n' => TokenKind::Null,
             _ => unreachable!(),
         })
     }
 
     fn read_escape_sequence(&mut self, result: &mut String) -> Result<(), LexerError> {
         self.advance(); // Skip the backslash
+        self.read_escaped_char(result)
+    }
+
+    /// Reads the character after a backslash and pushes what it stands for
+    fn read_escaped_char(&mut self, result: &mut String) -> Result<(), LexerError> {
         match self.peek() {
-            Some('\"') => {
+            Some(b'"') => {
                 result.push('\"');
                 self.advance();
             }
-            Some('\\') => {
+            Some(b'\\') => {
                 result.push('\\');
                 self.advance();
             }
-            Some('/') => {
+            Some(b'/') => {
                 result.push('/');
                 self.advance();
             }
-            Some('b') => {
+            Some(b'\'') if self.options.allows_single_quotes() => {
+                result.push('\'');
+                self.advance();
+            }
+            Some(b'b') => {
                 result.push('\u{0008}'); // Backspace
                 self.advance();
             }
-            Some('f') => {
+            Some(b'f') => {
                 result.push('\u{000C}'); // Formfeed
                 self.advance();
             }
-            Some('n') => {
+            Some(b'n') => {
                 result.push('\n');
                 self.advance();
             }
-            Some('r') => {
+            Some(b'r') => {
                 result.push('\r');
                 self.advance();
             }
-            Some('t') => {
+            Some(b't') => {
                 result.push('\t');
                 self.advance();
             }
-            Some('u') => {
+            Some(b'u') => {
                 self.advance(); // Skip 'u'
-                let mut hex = String::new();
-                for _ in 0..4 {
-                    match self.peek() {
-                        Some(c) if c.is_ascii_hexdigit() => {
-                            hex.push(c);
+                self.read_unicode_escape(result)?;
+            }
+            Some(_) if self.options.json5 => self.read_json5_escape(result)?,
+            Some(_) => {
+                let c = self.current_char().unwrap();
+                return Err(self.return_error(LexerErrorKinds::InvalidEscapeChar(c)));
+            }
+            None => return Err(self.return_error(LexerErrorKinds::InvalidEscape)),
+        }
+        Ok(())
+    }
+
+    /// The escapes JSON5 adds: `\v`, `\0`, `\xFF`, a backslash before a line break to continue
+    /// the string on the next line, and any other char standing for itself
+    fn read_json5_escape(&mut self, result: &mut String) -> Result<(), LexerError> {
+        let c = self.current_char().unwrap();
+        match c {
+            'v' => result.push('\u{000B}'),
+            '0' if !self.bytes.get(self.pos + 1).is_some_and(u8::is_ascii_digit) => {
+                result.push('\0');
+            }
+            '0'..='9' => return Err(self.return_error(LexerErrorKinds::InvalidEscapeChar(c))),
+            'x' => {
+                self.advance();
+                let mut code = 0;
+                for _ in 0..2 {
+                    match self.peek().and_then(|b| (b as char).to_digit(16)) {
+                        Some(digit) => {
+                            code = code * 16 + digit;
                             self.advance();
                         }
-                        _ => return Err(self.return_error(LexerErrorKinds::InvalidEscape)),
+                        None => return Err(self.return_error(LexerErrorKinds::InvalidEscape)),
                     }
                 }
-                let code_point = u32::from_str_radix(&hex, 16)
-                    .map_err(|_| self.return_error(LexerErrorKinds::InvalidEscape))?;
-                if let Some(ch) = char::from_u32(code_point) {
-                    result.push(ch);
-                } else {
-                    return Err(self.return_error(LexerErrorKinds::InvalidEscape));
+                // At most 0xFF, always a valid char
+                result.push(char::from_u32(code).unwrap());
+                return Ok(());
+            }
+            '\n' => {
+                self.advance();
+                return Ok(());
+            }
+            '\r' => {
+                self.advance();
+                if self.peek() == Some(b'\n') {
+                    self.advance();
                 }
+                return Ok(());
             }
-            Some(c) => return Err(self.return_error(LexerErrorKinds::InvalidEscapeChar(c))),
-            None => return Err(self.return_error(LexerErrorKinds::InvalidEscape)),
+            '\u{2028}' | '\u{2029}' => {}
+            _ => result.push(c),
         }
+        self.advance_by(c.len_utf8());
         Ok(())
     }
+
+    /// Reads the hex digits of a `\u` escape. Characters outside the basic multilingual plane
+    /// are written as a UTF-16 surrogate pair, `\uD83D\uDE00`, which we combine into one char.
+    fn read_unicode_escape(&mut self, result: &mut String) -> Result<(), LexerError> {
+        let mut unit = self.read_hex_code_unit()?;
+        loop {
+            match unit {
+                0xD800..=0xDBFF => {
+                    // High surrogate, only valid when directly followed by an escaped low one
+                    if self.peek() != Some(b'\\') {
+                        return self.push_lone_surrogate(result);
+                    }
+                    self.advance();
+                    if self.peek() != Some(b'u') {
+                        self.push_lone_surrogate(result)?;
+                        return self.read_escaped_char(result);
+                    }
+                    self.advance();
+                    let low = self.read_hex_code_unit()?;
+                    if (0xDC00..=0xDFFF).contains(&low) {
+                        let code_point = 0x10000 + ((unit - 0xD800) << 10) + (low - 0xDC00);
+                        // Always a valid scalar value, as it lies in 0x10000..=0x10FFFF
+                        result.push(char::from_u32(code_point).unwrap());
+                        return Ok(());
+                    }
+                    // The second escape may itself start a new pair
+                    self.push_lone_surrogate(result)?;
+                    unit = low;
+                }
+                0xDC00..=0xDFFF => return self.push_lone_surrogate(result),
+                _ => {
+                    // Everything outside the surrogate range is a valid scalar value
+                    result.push(char::from_u32(unit).unwrap());
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    fn read_hex_code_unit(&mut self) -> Result<u32, LexerError> {
+        let mut unit = 0;
+        for _ in 0..4 {
+            match self.peek().and_then(|b| (b as char).to_digit(16)) {
+                Some(digit) => {
+                    unit = unit * 16 + digit;
+                    self.advance();
+                }
+                None => return Err(self.return_error(LexerErrorKinds::InvalidEscape)),
+            }
+        }
+        Ok(unit)
+    }
+
+    /// A surrogate without its other half has no char, substitute U+FFFD in lossy mode
+    fn push_lone_surrogate(&self, result: &mut String) -> Result<(), LexerError> {
+        if self.options.lossy_surrogates {
+            result.push(char::REPLACEMENT_CHARACTER);
+            Ok(())
+        } else {
+            Err(self.return_error(LexerErrorKinds::LoneSurrogate))
+        }
+    }
+}
+
+/// Whitespace in JSON5 is any Unicode space separator, plus the byte order mark and the line
+/// and paragraph separators
+fn is_json5_space(c: char) -> bool {
+    matches!(
+        c,
+        '\u{00A0}' | '\u{FEFF}' | '\u{1680}' | '\u{2000}'..='\u{200A}'
+    ) || matches!(
+        c,
+        '\u{2028}' | '\u{2029}' | '\u{202F}' | '\u{205F}' | '\u{3000}'
+    )
+}
+
+/// Whether the integer part of a number lexeme has leading zeros, like `007` or `-01.5`
+fn has_leading_zero(raw: &str) -> bool {
+    let digits = raw.trim_start_matches(['-', '+']).as_bytes();
+    digits.len() > 1 && digits[0] == b'0' && digits[1].is_ascii_digit()
+}
+
+fn is_identifier_start(c: char) -> bool {
+    c == '$' || c == '_' || c.is_alphabetic()
+}
+
+fn is_identifier_part(c: char) -> bool {
+    is_identifier_start(c) || c.is_alphanumeric() || c == '\u{200C}' || c == '\u{200D}'
+}
+
+/// Number of chars in a run of valid UTF-8, that is every byte which is not a continuation byte
+#[inline(always)]
+fn char_count(bytes: &[u8]) -> usize {
+    bytes.iter().filter(|&&b| (b & 0xC0) != 0x80).count()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    fn lex_all(input: &str) -> Vec<TokenKind> {
+    fn lex_all(input: &str) -> Vec<TokenKind<'_>> {
         let mut lexer = Lexer::new(input);
         let mut tokens = Vec::new();
         loop {
@@ -420,10 +943,10 @@ mod tests {
     fn test_simple_numbers() {
         let input = "0 123 -456 -0.2e2";
         let expected = vec![
-            TokenKind::Number(0.0),
-            TokenKind::Number(123.0),
-            TokenKind::Number(-456.0),
-            TokenKind::Number(-20.0),
+            TokenKind::Number(0.into()),
+            TokenKind::Number(123.into()),
+            TokenKind::Number((-456).into()),
+            TokenKind::Number((-20.0).into()),
         ];
         assert_eq!(lex_all(input), expected);
     }
@@ -435,7 +958,7 @@ mod tests {
             TokenKind::LeftBrace,
             TokenKind::String("key".into()),
             TokenKind::Colon,
-            TokenKind::Number(-42.0),
+            TokenKind::Number((-42).into()),
             TokenKind::RightBrace,
         ];
         assert_eq!(lex_all(input), expected);
@@ -529,4 +1052,212 @@ mod tests {
             })
         ));
     }
+
+    #[test]
+    fn test_surrogate_pair() {
+        let input = r#""\ud83d\ude00 \uD834\uDD1E""#;
+        assert_eq!(lex_all(input), vec![TokenKind::String("😀 𝄞".into())]);
+    }
+
+    #[test]
+    fn test_lone_surrogate_error() {
+        for input in [
+            r#""\ud83d""#,
+            r#""\ude00""#,
+            r#""\ud83d\u0041""#,
+            r#""\ud83dx""#,
+        ] {
+            let mut lexer = Lexer::new(input);
+            let result = lexer.next_token();
+            assert!(
+                matches!(
+                    result,
+                    Err(LexerError {
+                        kind: LexerErrorKinds::LoneSurrogate,
+                        ..
+                    })
+                ),
+                "{} gave {:?}",
+                input,
+                result
+            );
+        }
+    }
+
+    #[test]
+    fn test_lossy_surrogates() {
+        let options = ParserOptions::new().lossy_surrogates(true);
+        let lex_lossy = |input| {
+            Lexer::with_options(input, &options)
+                .next_token()
+                .unwrap()
+                .kind
+        };
+        assert_eq!(
+            lex_lossy(r#""a\ud83db""#),
+            TokenKind::String("a\u{FFFD}b".into())
+        );
+        assert_eq!(
+            lex_lossy(r#""\ud83d\u0041\n""#),
+            TokenKind::String("\u{FFFD}A\n".into())
+        );
+        assert_eq!(
+            lex_lossy(r#""\ud800\ud83d\ude00""#),
+            TokenKind::String("\u{FFFD}😀".into())
+        );
+    }
+
+    #[test]
+    fn test_strings_borrow_unless_escaped() {
+        let tokens = lex_all(r#""plain" "esc\naped""#);
+        assert!(matches!(
+            &tokens[0],
+            TokenKind::String(Cow::Borrowed("plain"))
+        ));
+        assert!(matches!(&tokens[1], TokenKind::String(Cow::Owned(s)) if s == "esc\naped"));
+    }
+
+    #[test]
+    fn test_columns_count_chars() {
+        let columns: Vec<(usize, usize)> = Lexer::new("[\"γάμμα😀\", 1,\n  [é]")
+            .take(6)
+            .map(|t| t.unwrap())
+            .map(|t| (t.line, t.column))
+            .collect();
+        assert_eq!(columns[..3], [(1, 1), (1, 2), (1, 10)]);
+        assert_eq!(columns[5], (2, 3));
+
+        let err = Lexer::new("[\"γά\", 1,\n  é]").nth(5).unwrap().unwrap_err();
+        assert_eq!(err.kind, LexerErrorKinds::UnexcpectedChar('é'));
+        assert_eq!((err.line, err.column), (2, 3));
+    }
+
+    #[test]
+    fn test_spans_cover_lexemes() {
+        let input = "[-12.5e3, true,\n \"é\\n\"]";
+        let tokens: Vec<Token> = Lexer::new(input).map(|t| t.unwrap()).collect();
+        let text: Vec<&str> = tokens
+            .iter()
+            .map(|t| &input[t.span.start..t.span.end])
+            .collect();
+        assert_eq!(
+            text,
+            ["[", "-12.5e3", ",", "true", ",", "\"é\\n\"", "]", ""]
+        );
+
+        // Numbers and literals used to be stamped with the position after them
+        assert_eq!((tokens[1].line, tokens[1].column), (1, 2));
+        assert_eq!((tokens[3].line, tokens[3].column), (1, 11));
+        let string = tokens[5].span;
+        assert_eq!((string.start_line, string.start_column), (2, 2));
+        assert_eq!((string.end_line, string.end_column), (2, 7));
+    }
+
+    #[test]
+    fn test_error_spans() {
+        let input = "[1, fals]";
+        let err = Lexer::new(input).nth(3).unwrap().unwrap_err();
+        assert_eq!(&input[err.span.start..err.span.end], "fals]");
+        assert_eq!((err.line, err.column), (1, 9));
+
+        let input = "\"ab\\q\"";
+        let err = Lexer::new(input).next().unwrap().unwrap_err();
+        assert_eq!(&input[err.span.start..err.span.end], "\"ab\\q");
+
+        // The newline that ends an unclosed string is not part of it
+        let input = "\"ab\ncd\"";
+        let err = Lexer::new(input).next().unwrap().unwrap_err();
+        assert_eq!(&input[err.span.start..err.span.end], "\"ab");
+        assert_eq!((err.span.end_line, err.span.end_column), (1, 4));
+    }
+
+    #[test]
+    fn test_comments() {
+        let options = ParserOptions::new().comments(true);
+        let input = "[1// a\n/* b\n c */,//\n2/**/]";
+        let tokens: Vec<TokenKind> = Lexer::with_options(input, &options)
+            .map(|t| t.unwrap().kind)
+            .collect();
+        assert_eq!(
+            tokens,
+            [
+                TokenKind::LeftBracket,
+                TokenKind::Number(1.into()),
+                TokenKind::Comma,
+                TokenKind::Number(2.into()),
+                TokenKind::RightBracket,
+                TokenKind::EOF
+            ]
+        );
+
+        // Slashes that do not start a comment stay errors
+        let err = Lexer::with_options("[1 / 2]", &options).nth(2).unwrap();
+        assert_eq!(err.unwrap_err().kind, LexerErrorKinds::UnexcpectedChar('/'));
+        let err = Lexer::with_options("/* é *", &options).next_token();
+        let err = err.unwrap_err();
+        assert_eq!(err.kind, LexerErrorKinds::UnterminatedComment);
+        assert_eq!((err.span.start, err.span.end_column), (0, 7));
+    }
+
+    #[test]
+    fn test_comments_as_trivia() {
+        let options = ParserOptions::new().comments(true);
+        let input = "{ // a\n/* b\n c */}";
+        let tokens: Vec<Token> = Tokens::new(input, &options)
+            .trivia(true)
+            .map(|t| t.unwrap())
+            .collect();
+        let comments: Vec<(&str, usize, usize)> = tokens
+            .iter()
+            .filter(|t| matches!(t.kind, TokenKind::Comment(_)))
+            .map(|t| (&input[t.span.start..t.span.end], t.line, t.column))
+            .collect();
+        assert_eq!(comments, [("// a", 1, 3), ("/* b\n c */", 2, 1)]);
+        assert_eq!(tokens[3].kind, TokenKind::RightBrace);
+        assert_eq!((tokens[3].line, tokens[3].column), (3, 6));
+    }
+
+    #[test]
+    fn test_json5_numbers_and_strings() {
+        let options = ParserOptions::new().json5(true);
+        let lex = |input| -> Vec<TokenKind> {
+            Lexer::with_options(input, &options)
+                .map(|t| t.unwrap().kind)
+                .filter(|kind| *kind != TokenKind::EOF)
+                .collect()
+        };
+        let numbers: Vec<String> = lex("0x1F -0XfF +.5 5. 1.e2 +Infinity -NaN")
+            .iter()
+            .map(|kind| match kind {
+                TokenKind::Number(n) => format!("{} {}", n.as_str(), n),
+                _ => panic!("Expected a number, got {}", kind),
+            })
+            .collect();
+        assert_eq!(
+            numbers,
+            [
+                "0x1F 31",
+                "-0XfF -255",
+                "+.5 0.5",
+                "5. 5.0",
+                "1.e2 100.0",
+                "+Infinity 1e999",
+                "-NaN null"
+            ]
+        );
+
+        assert_eq!(
+            lex("'a\\'\"\\x41\\v\\0' \"\t\\q\"\u{00A0}\u{FEFF}$é_1"),
+            [
+                TokenKind::String("a'\"A\u{b}\0".into()),
+                TokenKind::String("\tq".into()),
+                TokenKind::Identifier("$é_1".into()),
+            ]
+        );
+
+        for input in ["0x", ".", "+Inf", "'\\1'", "\"\\x4\""] {
+            let result = Lexer::with_options(input, &options).next_token();
+            assert!(result.is_err(), "{} gave {:?}", input, result);
+        }
+    }
 }
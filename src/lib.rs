@@ -1,6 +1,40 @@
+pub mod diagnostic;
+pub mod elements;
 pub mod errors;
+pub mod handler;
 pub mod jsonvalue;
+pub mod lazy;
 pub mod lexer;
+pub mod ndjson;
+pub mod number;
+pub mod object;
+pub mod options;
 pub mod parser;
+pub mod pull;
+pub mod push;
+pub mod reader;
+pub mod serializer;
+pub mod span;
+pub mod spanned;
+pub mod stream;
 
-pub use crate::parser::parse_json;
+pub use crate::elements::{ArrayElements, ArrayElementsReader};
+pub use crate::handler::{Handler, parse_json_events, parse_json_events_with};
+pub use crate::jsonvalue::JSONValue;
+pub use crate::lazy::{LazyValue, RawValue, parse_lazy, parse_lazy_with};
+pub use crate::ndjson::{JsonLines, JsonLinesReader};
+pub use crate::number::Number;
+pub use crate::object::JSONObject;
+pub use crate::options::{DuplicateKeys, ParserOptions};
+pub use crate::parser::{
+    Recovered, parse_json, parse_json_arbitrary_precision, parse_json_lossy, parse_json_recovering,
+    parse_json_recovering_with, parse_json_spanned, parse_json_spanned_with, parse_json_with,
+    parse_json_with_max_depth,
+};
+pub use crate::pull::{Event, PullParser};
+pub use crate::push::PushParser;
+pub use crate::reader::{parse_reader, parse_reader_with};
+pub use crate::serializer::{to_string, to_string_pretty};
+pub use crate::span::Span;
+pub use crate::spanned::SpannedValue;
+pub use crate::stream::{JsonSeq, JsonStream};
@@ -32,6 +32,40 @@ fn test_valid_json_files() {
     }
 }
 
+#[test]
+fn test_valid_json_files_round_trip() {
+    use rust_json_parser::{to_string, to_string_pretty};
+    use std::fs;
+
+    for i in 1..=44 {
+        let path = format!("tests/valid{}.json", i);
+        let input = fs::read_to_string(&path).unwrap_or_else(|_| panic!("Failed to read {}", path));
+        let value = parse_json(&input).unwrap();
+
+        for output in [to_string(&value), to_string_pretty(&value)] {
+            let reparsed = parse_json(&output)
+                .unwrap_or_else(|e| panic!("Failed to reparse {}: {}\n{}", path, e, output));
+            assert_eq!(reparsed, value, "Round trip changed {}", path);
+        }
+    }
+}
+
+#[test]
+fn test_valid_json_files_as_json5() {
+    use rust_json_parser::{ParserOptions, parse_json_with};
+    use std::fs;
+
+    // JSON5 is a superset of JSON, every valid file means the same in both
+    let options = ParserOptions::new().json5(true);
+    for i in 1..=44 {
+        let path = format!("tests/valid{}.json", i);
+        let input = fs::read_to_string(&path).unwrap_or_else(|_| panic!("Failed to read {}", path));
+        let json5 = parse_json_with(&input, &options)
+            .unwrap_or_else(|e| panic!("Failed to parse {} as JSON5: {}", path, e));
+        assert_eq!(json5, parse_json(&input).unwrap(), "JSON5 changed {}", path);
+    }
+}
+
 #[test]
 fn test_invalid_json_files_by_chat() {
     use std::fs;
@@ -57,3 +91,23 @@ fn test_invalid_json_files() {
         assert!(result.is_err(), "Should fail to parse {}", path);
     }
 }
+
+#[test]
+fn test_json_files_from_reader() {
+    use rust_json_parser::{errors::ReadError, parse_reader};
+    use std::fs;
+
+    let paths = (1..=44)
+        .map(|i| format!("tests/valid{}.json", i))
+        .chain((1..=41).map(|i| format!("tests/invalid{}.json", i)))
+        .chain((1..=33).map(|i| format!("tests/fail{}.json", i)));
+    for path in paths {
+        let input = fs::read_to_string(&path).unwrap_or_else(|_| panic!("Failed to read {}", path));
+        let file = fs::File::open(&path).unwrap();
+        match (parse_reader(file), parse_json(&input)) {
+            (Ok(value), Ok(expected)) => assert_eq!(value, expected, "Reader changed {}", path),
+            (Err(ReadError::Parse(e)), Err(expected)) => assert_eq!(e, expected, "{}", path),
+            (result, _) => panic!("Reader gave {:?} for {}", result, path),
+        }
+    }
+}
@@ -0,0 +1,273 @@
+use std::io::BufRead;
+
+use crate::{
+    errors::{ParserError, ReadError},
+    jsonvalue::JSONValue,
+    options::ParserOptions,
+    parser::parse_json_with,
+};
+
+/// Iterates over the values of newline delimited JSON (NDJSON, JSON Lines), one value per line.
+/// Errors give their position in the whole input, not in the line.
+///
+/// Blank and malformed lines are errors unless `skip_blank_lines` or `skip_malformed_lines`
+/// is set, in which case they are skipped and counted instead.
+pub struct JsonLines<'a> {
+    rest: &'a str,
+    offset: usize, // Byte offset of rest in the input
+    lines: LineParser,
+}
+
+/// Like `JsonLines`, but reads the lines from a `BufRead`. Values are detached from the line
+/// they came from, as the buffer is reused for the next one.
+pub struct JsonLinesReader<R> {
+    reader: R,
+    buffer: Vec<u8>,
+    offset: usize,
+    lines: LineParser,
+}
+
+/// What both iterators share: the options, the line count and the skipping
+struct LineParser {
+    options: ParserOptions,
+    line: usize, // Number of lines read so far
+    skip_blank: bool,
+    skip_malformed: bool,
+    blank: usize,
+    malformed: usize,
+}
+
+impl LineParser {
+    fn new(options: &ParserOptions) -> Self {
+        Self {
+            options: options.clone(),
+            line: 0,
+            skip_blank: false,
+            skip_malformed: false,
+            blank: 0,
+            malformed: 0,
+        }
+    }
+
+    /// Parses the next line, which starts `offset` bytes into the input. None when the line
+    /// is skipped.
+    fn parse<'b>(
+        &mut self,
+        line: &'b str,
+        offset: usize,
+    ) -> Option<Result<JSONValue<'b>, ParserError>> {
+        self.line += 1;
+        if self.skip_blank && line.trim_ascii().is_empty() {
+            self.blank += 1;
+            return None;
+        }
+        match parse_json_with(line, &self.options) {
+            Ok(value) => Some(Ok(value)),
+            Err(_) if self.skip_malformed => {
+                self.malformed += 1;
+                None
+            }
+            Err(e) => Some(Err(e.offset(offset, self.line - 1))),
+        }
+    }
+
+    /// Invalid UTF-8 counts as a malformed line
+    fn skip_invalid_utf8(&mut self) -> bool {
+        self.line += 1;
+        if self.skip_malformed {
+            self.malformed += 1;
+        }
+        self.skip_malformed
+    }
+}
+
+impl<'a> JsonLines<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Self::with_options(input, &ParserOptions::default())
+    }
+
+    /// Each line is parsed with the given options
+    pub fn with_options(input: &'a str, options: &ParserOptions) -> Self {
+        Self {
+            rest: input,
+            offset: 0,
+            lines: LineParser::new(options),
+        }
+    }
+
+    /// Skip lines that hold nothing but whitespace instead of failing on them
+    pub fn skip_blank_lines(mut self, skip: bool) -> Self {
+        self.lines.skip_blank = skip;
+        self
+    }
+
+    /// Skip lines that fail to parse instead of yielding their error
+    pub fn skip_malformed_lines(mut self, skip: bool) -> Self {
+        self.lines.skip_malformed = skip;
+        self
+    }
+
+    /// Number of blank lines skipped so far
+    pub fn blank_lines(&self) -> usize {
+        self.lines.blank
+    }
+
+    /// Number of malformed lines skipped so far
+    pub fn malformed_lines(&self) -> usize {
+        self.lines.malformed
+    }
+}
+
+impl<'a> Iterator for JsonLines<'a> {
+    type Item = Result<JSONValue<'a>, ParserError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // A newline at the very end does not start another line
+        while !self.rest.is_empty() {
+            let (line, rest) = match self.rest.find('\n') {
+                Some(end) => (&self.rest[..end], &self.rest[end + 1..]),
+                None => (self.rest, ""),
+            };
+            let offset = self.offset;
+            self.offset += self.rest.len() - rest.len();
+            self.rest = rest;
+            if let Some(result) = self.lines.parse(line, offset) {
+                return Some(result);
+            }
+        }
+        None
+    }
+}
+
+impl<R: BufRead> JsonLinesReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self::with_options(reader, &ParserOptions::default())
+    }
+
+    /// Each line is parsed with the given options
+    pub fn with_options(reader: R, options: &ParserOptions) -> Self {
+        Self {
+            reader,
+            buffer: Vec::new(),
+            offset: 0,
+            lines: LineParser::new(options),
+        }
+    }
+
+    /// See `JsonLines::skip_blank_lines`
+    pub fn skip_blank_lines(mut self, skip: bool) -> Self {
+        self.lines.skip_blank = skip;
+        self
+    }
+
+    /// See `JsonLines::skip_malformed_lines`. Lines that are not valid UTF-8 are skipped too.
+    pub fn skip_malformed_lines(mut self, skip: bool) -> Self {
+        self.lines.skip_malformed = skip;
+        self
+    }
+
+    /// Number of blank lines skipped so far
+    pub fn blank_lines(&self) -> usize {
+        self.lines.blank
+    }
+
+    /// Number of malformed lines skipped so far
+    pub fn malformed_lines(&self) -> usize {
+        self.lines.malformed
+    }
+}
+
+impl<R: BufRead> Iterator for JsonLinesReader<R> {
+    type Item = Result<JSONValue<'static>, ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.buffer.clear();
+            match self.reader.read_until(b'\n', &mut self.buffer) {
+                Ok(0) => return None,
+                Ok(len) => {
+                    let offset = self.offset;
+                    self.offset += len;
+                    let line = self.buffer.strip_suffix(b"\n").unwrap_or(&self.buffer);
+                    let line = match std::str::from_utf8(line) {
+                        Ok(line) => line,
+                        Err(_) if self.lines.skip_invalid_utf8() => continue,
+                        Err(e) => {
+                            let at = offset + e.valid_up_to();
+                            let message =
+                                format!("invalid UTF-8 at byte {} on line {}", at, self.lines.line);
+                            let error =
+                                std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+                            return Some(Err(error.into()));
+                        }
+                    };
+                    if let Some(result) = self.lines.parse(line, offset) {
+                        return Some(result.map(JSONValue::into_owned).map_err(ReadError::from));
+                    }
+                }
+                Err(e) => return Some(Err(e.into())),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const INPUT: &str = "{\"a\": 1}\n\n[2, 3]\r\n{\"b\": tru}\n\"x\"\n";
+
+    #[test]
+    fn test_values_and_error_positions() {
+        let results: Vec<_> = JsonLines::new(INPUT).collect();
+        assert_eq!(results.len(), 5);
+        assert_eq!(results[0].as_ref().unwrap().to_string(), r#"{"a":1}"#);
+        assert_eq!(results[1].as_ref().unwrap_err().line(), 2);
+        assert_eq!(results[2].as_ref().unwrap().to_string(), "[2,3]");
+
+        // Positions count from the start of the input
+        let err = results[3].as_ref().unwrap_err();
+        assert_eq!((err.line(), err.code()), (4, "invalid_literal"));
+        let span = err.span();
+        assert_eq!(&INPUT[span.start..span.end], "tru}");
+        assert_eq!(span.start_line, 4);
+        assert_eq!(results[4].as_ref().unwrap().to_string(), "\"x\"");
+    }
+
+    #[test]
+    fn test_skipping_and_counting() {
+        let mut lines = JsonLines::new(INPUT)
+            .skip_blank_lines(true)
+            .skip_malformed_lines(true);
+        let values: Vec<String> = lines.by_ref().map(|v| v.unwrap().to_string()).collect();
+        assert_eq!(values, [r#"{"a":1}"#, "[2,3]", "\"x\""]);
+        assert_eq!((lines.blank_lines(), lines.malformed_lines()), (1, 1));
+
+        // Without a newline at the end the last line still counts
+        let values: Vec<_> = JsonLines::new("1\n2").map(|v| v.unwrap()).collect();
+        assert_eq!(values.len(), 2);
+    }
+
+    #[test]
+    fn test_reader() {
+        let mut input = INPUT.as_bytes().to_vec();
+        input.extend_from_slice(b"\"\xff\"\n[4]");
+        let mut lines = JsonLinesReader::new(Cursor::new(&input)).skip_blank_lines(true);
+        let results: Vec<_> = lines.by_ref().collect();
+        assert_eq!(results.len(), 6);
+        assert_eq!(results[1].as_ref().unwrap().to_string(), "[2,3]");
+        assert!(matches!(&results[2], Err(ReadError::Parse(e)) if e.line() == 4));
+        assert!(matches!(&results[4], Err(ReadError::Io(_))));
+        assert_eq!(
+            results[4].as_ref().unwrap_err().to_string(),
+            "I/O error: invalid UTF-8 at byte 34 on line 6"
+        );
+        assert_eq!(results[5].as_ref().unwrap().to_string(), "[4]");
+        assert_eq!(lines.blank_lines(), 1);
+
+        let mut lines = JsonLinesReader::new(Cursor::new(&input)).skip_malformed_lines(true);
+        assert_eq!(lines.by_ref().filter(|v| v.is_ok()).count(), 4);
+        assert_eq!(lines.malformed_lines(), 3);
+    }
+}
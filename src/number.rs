@@ -0,0 +1,324 @@
+use std::borrow::Cow;
+use std::fmt;
+
+/// A JSON number. Integers that fit are stored as `u64`/`i64` so they survive exactly,
+/// everything else as `f64`. The text the number was written as is always kept as well.
+///
+/// In arbitrary precision mode numbers that an `f64` would round are not converted at all,
+/// they stay as their lexeme and `as_f64` converts on demand, so no digits are ever lost.
+#[derive(Clone)]
+pub struct Number<'a> {
+    value: N,
+    raw: Cow<'a, str>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum N {
+    /// Always non-negative
+    PosInt(u64),
+    /// Always negative
+    NegInt(i64),
+    Float(f64),
+    /// Only produced in arbitrary precision mode, the value lives in `raw`
+    Arbitrary,
+}
+
+/// Sign, significant digits and decimal exponent of a number written in decimal, so that `15`,
+/// `1.50e1` and `150e-1` give the same. None for anything else, such as `inf`.
+fn decimal(text: &str) -> Option<(bool, String, i64)> {
+    let (negative, text) = match text.strip_prefix('-') {
+        Some(text) => (true, text),
+        None => (false, text),
+    };
+    let (mantissa, exponent) = match text.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => (mantissa, exponent.parse::<i64>().ok()?),
+        None => (text, 0),
+    };
+    let (int, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    if !int
+        .bytes()
+        .chain(fraction.bytes())
+        .all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+    let all = format!("{}{}", int, fraction);
+    let digits = all.trim_start_matches('0');
+    // The value is 0.digits times ten to the power of point
+    let point = exponent.checked_add(int.len() as i64 - (all.len() - digits.len()) as i64)?;
+    let digits = digits.trim_end_matches('0');
+    Some((
+        negative,
+        digits.to_string(),
+        if digits.is_empty() { 0 } else { point },
+    ))
+}
+
+impl<'a> Number<'a> {
+    /// Builds a number from a lexeme the lexer has already validated against the JSON grammar.
+    /// `is_integer` is true when the lexeme has neither a fraction nor an exponent.
+    pub(crate) fn from_lexeme(
+        raw: Cow<'a, str>,
+        is_integer: bool,
+        arbitrary_precision: bool,
+    ) -> Option<Self> {
+        let int_value = if !is_integer || raw.as_ref() == "-0" {
+            // -0 has to stay a float to keep its sign
+            None
+        } else if raw.starts_with('-') {
+            raw.parse::<i64>().ok().map(N::NegInt)
+        } else {
+            raw.parse::<u64>().ok().map(N::PosInt)
+        };
+
+        let value = match int_value {
+            Some(value) => value,
+            None => {
+                let float = raw.parse::<f64>().ok()?;
+                // Shortest form that reads back as the same f64, so different digits mean the
+                // f64 has rounded the number
+                if arbitrary_precision && decimal(&raw) != decimal(&format!("{:e}", float)) {
+                    N::Arbitrary
+                } else {
+                    N::Float(float)
+                }
+            }
+        };
+        Some(Self { value, raw })
+    }
+
+    /// Builds a number from a JSON5 hexadecimal lexeme such as `-0x1F`, given the digits after
+    /// the `0x`. Values too large for an integer become floats, even in arbitrary precision
+    /// mode, as the lexeme is no JSON number.
+    pub(crate) fn from_hex_lexeme(raw: Cow<'a, str>, negative: bool, digits: &str) -> Self {
+        let value = match u64::from_str_radix(digits, 16) {
+            // -0 has to stay a float to keep its sign, like in from_lexeme
+            Ok(0) if negative => N::Float(-0.0),
+            Ok(n) if negative => match 0i64.checked_sub_unsigned(n) {
+                Some(n) => N::NegInt(n),
+                None => N::Float(-(n as f64)),
+            },
+            Ok(n) => N::PosInt(n),
+            Err(_) => {
+                let n = digits.chars().fold(0.0, |acc, d| {
+                    acc * 16.0 + f64::from(d.to_digit(16).unwrap_or(0))
+                });
+                N::Float(if negative { -n } else { n })
+            }
+        };
+        Self { value, raw }
+    }
+
+    /// The number exactly as it was written in the input
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+
+    pub fn is_i64(&self) -> bool {
+        self.as_i64().is_some()
+    }
+
+    pub fn is_u64(&self) -> bool {
+        matches!(self.value, N::PosInt(_))
+    }
+
+    pub fn is_f64(&self) -> bool {
+        matches!(self.value, N::Float(_))
+    }
+
+    /// True for numbers kept as their text because an `f64` would round them, which only
+    /// happens with `ParserOptions::arbitrary_precision`
+    pub fn is_arbitrary(&self) -> bool {
+        matches!(self.value, N::Arbitrary)
+    }
+
+    /// Some if the number is an integer that fits in an `i64`
+    pub fn as_i64(&self) -> Option<i64> {
+        match self.value {
+            N::PosInt(n) => i64::try_from(n).ok(),
+            N::NegInt(n) => Some(n),
+            N::Float(_) | N::Arbitrary => None,
+        }
+    }
+
+    /// Some if the number is an integer that fits in a `u64`
+    pub fn as_u64(&self) -> Option<u64> {
+        match self.value {
+            N::PosInt(n) => Some(n),
+            N::NegInt(_) | N::Float(_) | N::Arbitrary => None,
+        }
+    }
+
+    /// The closest `f64`, which may round large integers and long fractions
+    pub fn as_f64(&self) -> f64 {
+        match self.value {
+            N::PosInt(n) => n as f64,
+            N::NegInt(n) => n as f64,
+            N::Float(n) => n,
+            // The lexeme passed the JSON number grammar, which f64 parsing accepts
+            N::Arbitrary => self.raw.parse().unwrap_or(f64::NAN),
+        }
+    }
+
+    /// Copies the lexeme if it was borrowed from the input
+    pub fn into_owned(self) -> Number<'static> {
+        Number {
+            value: self.value,
+            raw: Cow::Owned(self.raw.into_owned()),
+        }
+    }
+}
+
+impl PartialEq for Number<'_> {
+    /// Integers and floats never compare equal, `1` and `1.0` are different numbers. Numbers in
+    /// arbitrary precision form are compared by their text.
+    fn eq(&self, other: &Self) -> bool {
+        match (self.value, other.value) {
+            (N::PosInt(a), N::PosInt(b)) => a == b,
+            (N::NegInt(a), N::NegInt(b)) => a == b,
+            (N::Float(a), N::Float(b)) => a == b,
+            (N::Arbitrary, N::Arbitrary) => self.raw == other.raw,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Debug for Number<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Number({})", self.raw)
+    }
+}
+
+/// Writes the number as valid JSON. Floats use the shortest form that round trips and always
+/// carry a fraction or exponent, so they read back as floats.
+impl fmt::Display for Number<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.value {
+            N::PosInt(n) => write!(f, "{}", n),
+            N::NegInt(n) => write!(f, "{}", n),
+            // JSON has no way to express NaN
+            N::Float(n) if n.is_nan() => f.write_str("null"),
+            // Overflowing literals such as 1e400 parse to infinity, so write one back
+            N::Float(n) if n.is_infinite() => f.write_str(if n > 0.0 { "1e999" } else { "-1e999" }),
+            // Debug prints "1.0" rather than "1", and switches to exponents for large values
+            N::Float(n) => write!(f, "{:?}", n),
+            N::Arbitrary => f.write_str(&self.raw),
+        }
+    }
+}
+
+impl From<i64> for Number<'_> {
+    fn from(n: i64) -> Self {
+        let value = if n < 0 {
+            N::NegInt(n)
+        } else {
+            N::PosInt(n as u64)
+        };
+        Number {
+            value,
+            raw: Cow::Owned(n.to_string()),
+        }
+    }
+}
+
+impl From<i32> for Number<'_> {
+    fn from(n: i32) -> Self {
+        Number::from(i64::from(n))
+    }
+}
+
+impl From<u32> for Number<'_> {
+    fn from(n: u32) -> Self {
+        Number::from(u64::from(n))
+    }
+}
+
+impl From<u64> for Number<'_> {
+    fn from(n: u64) -> Self {
+        Number {
+            value: N::PosInt(n),
+            raw: Cow::Owned(n.to_string()),
+        }
+    }
+}
+
+impl From<f64> for Number<'_> {
+    fn from(n: f64) -> Self {
+        Number {
+            value: N::Float(n),
+            raw: Cow::Owned(format!("{:?}", n)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lexeme(raw: &str, arbitrary_precision: bool) -> Number<'_> {
+        let is_integer = !raw.contains(['.', 'e', 'E']);
+        Number::from_lexeme(Cow::Borrowed(raw), is_integer, arbitrary_precision).unwrap()
+    }
+
+    #[test]
+    fn test_integers_stay_exact() {
+        let n = lexeme("9007199254740993", false);
+        assert_eq!(n.as_i64(), Some(9007199254740993));
+        assert_eq!(n.as_u64(), Some(9007199254740993));
+        assert_eq!(n.to_string(), "9007199254740993");
+
+        let n = lexeme("18446744073709551615", false);
+        assert_eq!(n.as_u64(), Some(u64::MAX));
+        assert_eq!(n.as_i64(), None);
+
+        let n = lexeme("-9223372036854775808", false);
+        assert_eq!(n.as_i64(), Some(i64::MIN));
+        assert_eq!(n.as_u64(), None);
+    }
+
+    #[test]
+    fn test_floats() {
+        let n = lexeme("1.50", false);
+        assert!(n.is_f64());
+        assert_eq!(n.as_f64(), 1.5);
+        assert_eq!(n.as_str(), "1.50");
+        assert_eq!(n.to_string(), "1.5");
+        assert_eq!(lexeme("1E2", false).to_string(), "100.0");
+        assert_eq!(lexeme("-0", false).to_string(), "-0.0");
+        assert_ne!(lexeme("1.0", false), lexeme("1", false));
+    }
+
+    #[test]
+    fn test_overflowing_integer_becomes_float() {
+        let n = lexeme("123456789012345678901234567890", false);
+        assert!(n.is_f64());
+        assert_eq!(n.as_f64(), 1.2345678901234568e29);
+        assert_eq!(n.as_str(), "123456789012345678901234567890");
+    }
+
+    #[test]
+    fn test_arbitrary_precision_keeps_digits() {
+        let big = "123456789012345678901234567890.000000000000000000001";
+        let n = lexeme(big, true);
+        assert_eq!(n.to_string(), big);
+        assert_eq!(n.as_f64(), 1.2345678901234568e29);
+        assert_eq!(n.as_i64(), None);
+        assert!(n.is_arbitrary());
+        assert_ne!(n, lexeme("123456789012345678901234567890", true));
+        // Integers that fit are still stored as integers
+        assert_eq!(lexeme("42", true).as_u64(), Some(42));
+        // As are floats that an f64 holds without rounding
+        for exact in ["1.50", "-0.015", "1e2", "-0", "2.5E-3", "0.1"] {
+            let n = lexeme(exact, true);
+            assert!(n.is_f64() && !n.is_arbitrary(), "{}", exact);
+        }
+        for rounded in [
+            "0.10000000000000000001",
+            "1e400",
+            "1e-400",
+            "9007199254740993.0",
+        ] {
+            assert!(lexeme(rounded, true).is_arbitrary(), "{}", rounded);
+        }
+    }
+}
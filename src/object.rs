@@ -0,0 +1,248 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Index;
+
+use crate::jsonvalue::JSONValue;
+
+/// Objects with at most this many members are searched linearly, larger ones get a hash index
+const INDEX_THRESHOLD: usize = 8;
+
+/// Map of object members that remembers the order keys were inserted in.
+///
+/// Members live in a `Vec`, so iteration follows the source document. Lookups scan the
+/// members for small objects and go through a `HashMap` of positions once the object grows.
+/// Equality ignores order, two objects are equal when they hold the same members.
+#[derive(Default)]
+pub struct JSONObject<'a> {
+    entries: Vec<(Cow<'a, str>, JSONValue<'a>)>,
+    index: HashMap<Cow<'a, str>, usize>,
+}
+
+impl<'a> JSONObject<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            entries: Vec::with_capacity(capacity),
+            index: HashMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn position(&self, key: &str) -> Option<usize> {
+        if self.entries.len() <= INDEX_THRESHOLD {
+            self.entries.iter().position(|(k, _)| k == key)
+        } else {
+            self.index.get(key).copied()
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&JSONValue<'a>> {
+        self.position(key).map(|i| &self.entries[i].1)
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut JSONValue<'a>> {
+        self.position(key).map(|i| &mut self.entries[i].1)
+    }
+
+    /// Position of the key in insertion order
+    pub fn index_of(&self, key: &str) -> Option<usize> {
+        self.position(key)
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.position(key).is_some()
+    }
+
+    /// Inserts a member at the end of the object. If the key is already present its value
+    /// is replaced in place, keeping the original position, and the old value is returned.
+    pub fn insert(
+        &mut self,
+        key: impl Into<Cow<'a, str>>,
+        value: JSONValue<'a>,
+    ) -> Option<JSONValue<'a>> {
+        let key = key.into();
+        if let Some(i) = self.position(&key) {
+            return Some(std::mem::replace(&mut self.entries[i].1, value));
+        }
+
+        if self.entries.len() == INDEX_THRESHOLD {
+            // Crossing the threshold, index everything inserted so far
+            self.index.reserve(self.entries.capacity());
+            for (i, (k, _)) in self.entries.iter().enumerate() {
+                self.index.insert(k.clone(), i);
+            }
+        }
+        if self.entries.len() >= INDEX_THRESHOLD {
+            self.index.insert(key.clone(), self.entries.len());
+        }
+        self.entries.push((key, value));
+        None
+    }
+
+    /// Removes a member, shifting the following members down to keep their order
+    pub fn remove(&mut self, key: &str) -> Option<JSONValue<'a>> {
+        let i = self.position(key)?;
+        let (key, value) = self.entries.remove(i);
+        if self.entries.len() <= INDEX_THRESHOLD {
+            // Small enough to be searched linearly again
+            self.index.clear();
+        } else {
+            self.index.remove(&key);
+            for position in self.index.values_mut() {
+                if *position > i {
+                    *position -= 1;
+                }
+            }
+        }
+        Some(value)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &JSONValue<'a>)> {
+        self.entries.iter().map(|(k, v)| (k.as_ref(), v))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&str, &mut JSONValue<'a>)> {
+        self.entries.iter_mut().map(|(k, v)| (&**k, v))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|(k, _)| k.as_ref())
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &JSONValue<'a>> {
+        self.entries.iter().map(|(_, v)| v)
+    }
+
+    /// Copies every borrowed key and value, see `JSONValue::into_owned`
+    pub fn into_owned(self) -> JSONObject<'static> {
+        self.entries
+            .into_iter()
+            .map(|(k, v)| (Cow::Owned(k.into_owned()), v.into_owned()))
+            .collect()
+    }
+}
+
+impl fmt::Debug for JSONObject<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl PartialEq for JSONObject<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().all(|(k, v)| other.get(k) == Some(v))
+    }
+}
+
+impl<'a> Index<&str> for JSONObject<'a> {
+    type Output = JSONValue<'a>;
+
+    fn index(&self, key: &str) -> &JSONValue<'a> {
+        self.get(key)
+            .unwrap_or_else(|| panic!("key \"{}\" not found in object", key))
+    }
+}
+
+impl<'a, K: Into<Cow<'a, str>>> FromIterator<(K, JSONValue<'a>)> for JSONObject<'a> {
+    fn from_iter<T: IntoIterator<Item = (K, JSONValue<'a>)>>(iter: T) -> Self {
+        let iter = iter.into_iter();
+        let mut map = JSONObject::with_capacity(iter.size_hint().0);
+        for (k, v) in iter {
+            map.insert(k, v);
+        }
+        map
+    }
+}
+
+impl<'a> IntoIterator for JSONObject<'a> {
+    type Item = (Cow<'a, str>, JSONValue<'a>);
+    type IntoIter = std::vec::IntoIter<(Cow<'a, str>, JSONValue<'a>)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl<'m, 'a> IntoIterator for &'m JSONObject<'a> {
+    type Item = (&'m str, &'m JSONValue<'a>);
+    type IntoIter = std::iter::Map<
+        std::slice::Iter<'m, (Cow<'a, str>, JSONValue<'a>)>,
+        fn(&'m (Cow<'a, str>, JSONValue<'a>)) -> (&'m str, &'m JSONValue<'a>),
+    >;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter().map(|(k, v)| (k.as_ref(), v))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbered(n: usize) -> JSONObject<'static> {
+        (0..n)
+            .map(|i| (format!("key{}", i), JSONValue::Number((i as u64).into())))
+            .collect()
+    }
+
+    #[test]
+    fn test_insertion_order_is_kept() {
+        let map = numbered(20);
+        let keys: Vec<&str> = map.keys().collect();
+        let expected: Vec<String> = (0..20).map(|i| format!("key{}", i)).collect();
+        assert_eq!(keys, expected);
+        // Lookups work both below and above the index threshold
+        assert_eq!(map["key3"], JSONValue::Number(3.into()));
+        assert_eq!(map["key17"], JSONValue::Number(17.into()));
+        assert!(map.get("key20").is_none());
+    }
+
+    #[test]
+    fn test_insert_existing_key_keeps_position() {
+        let mut map = numbered(3);
+        let old = map.insert("key0", JSONValue::Null);
+        assert_eq!(old, Some(JSONValue::Number(0.into())));
+        assert_eq!(map.len(), 3);
+        assert_eq!(map.iter().next(), Some(("key0", &JSONValue::Null)));
+    }
+
+    #[test]
+    fn test_remove_reindexes() {
+        let mut map = numbered(12);
+        assert_eq!(map.remove("key2"), Some(JSONValue::Number(2.into())));
+        assert_eq!(map.len(), 11);
+        assert!(!map.contains_key("key2"));
+        assert_eq!(map["key11"], JSONValue::Number(11.into()));
+        assert_eq!(map.keys().nth(2).unwrap(), "key3");
+        assert_eq!(map.index_of("key3"), Some(2));
+        assert_eq!(map.index_of("key1"), Some(1));
+        // Down to the threshold, and back to searching linearly
+        for i in [0, 4, 6] {
+            map.remove(&format!("key{}", i));
+        }
+        assert_eq!(map.len(), INDEX_THRESHOLD);
+        assert_eq!(map.index_of("key11"), Some(7));
+        map.insert("key12", JSONValue::Null);
+        assert_eq!(map.index_of("key12"), Some(8));
+        assert_eq!(map.index_of("key11"), Some(7));
+    }
+
+    #[test]
+    fn test_equality_ignores_order() {
+        let a = numbered(10);
+        let b: JSONObject = numbered(10).into_iter().rev().collect();
+        assert_eq!(a, b);
+        assert_ne!(a, numbered(9));
+    }
+}
@@ -0,0 +1,151 @@
+/// How deeply arrays and objects may nest by default. Each level costs a few stack frames,
+/// so without a limit a document of nothing but `[` overflows the stack.
+pub const DEFAULT_MAX_DEPTH: usize = 128;
+
+/// What to do when an object contains the same key more than once
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicateKeys {
+    /// Fail with `ParserErrorKind::DuplicateKey`
+    Error,
+    /// Keep the value of the first occurrence
+    FirstWins,
+    /// Keep the value of the last occurrence, at the position of the first
+    #[default]
+    LastWins,
+    /// Gather the values of every occurrence into an array, in source order
+    CollectAll,
+}
+
+/// Settings shared by the lexer and parser, built up with the methods below. The defaults
+/// parse strict RFC 8259 JSON.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParserOptions {
+    pub(crate) max_depth: usize,
+    pub(crate) lossy_surrogates: bool,
+    pub(crate) arbitrary_precision: bool,
+    pub(crate) duplicate_keys: DuplicateKeys,
+    // The JSON5 features are None unless set, following `json5`, see the `allows_` methods
+    comments: Option<bool>,
+    pub(crate) json5: bool,
+    trailing_commas: Option<bool>,
+    nan_and_infinity: Option<bool>,
+    pub(crate) leading_zeros: bool,
+    control_characters: Option<bool>,
+    single_quotes: Option<bool>,
+}
+
+impl Default for ParserOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: DEFAULT_MAX_DEPTH,
+            lossy_surrogates: false,
+            arbitrary_precision: false,
+            duplicate_keys: DuplicateKeys::default(),
+            comments: None,
+            json5: false,
+            trailing_commas: None,
+            nan_and_infinity: None,
+            leading_zeros: false,
+            control_characters: None,
+            single_quotes: None,
+        }
+    }
+}
+
+impl ParserOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How deeply arrays and objects may nest before parsing fails with `DepthLimitExceeded`
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Replace unpaired UTF-16 surrogates in `\u` escapes with U+FFFD instead of failing
+    pub fn lossy_surrogates(mut self, lossy: bool) -> Self {
+        self.lossy_surrogates = lossy;
+        self
+    }
+
+    /// Keep numbers that an `f64` would round as their original text, see
+    /// `Number::is_arbitrary`
+    pub fn arbitrary_precision(mut self, arbitrary_precision: bool) -> Self {
+        self.arbitrary_precision = arbitrary_precision;
+        self
+    }
+
+    /// Policy for keys that appear more than once in the same object
+    pub fn duplicate_keys(mut self, policy: DuplicateKeys) -> Self {
+        self.duplicate_keys = policy;
+        self
+    }
+
+    /// Allow `//` line comments and `/* */` block comments wherever whitespace may go, as in
+    /// JSONC files like tsconfig.json or VS Code settings
+    pub fn comments(mut self, comments: bool) -> Self {
+        self.comments = Some(comments);
+        self
+    }
+
+    /// Parse JSON5 instead of JSON: unquoted keys, hexadecimal numbers, `+1`, `.5` and more.
+    /// The JSON5 features that have their own setting follow this one unless set themselves,
+    /// in any order. These are `comments`, `trailing_commas`, `nan_and_infinity`,
+    /// `control_characters` and `single_quotes`.
+    pub fn json5(mut self, json5: bool) -> Self {
+        self.json5 = json5;
+        self
+    }
+
+    /// Allow a single comma after the last element of an array or member of an object
+    pub fn trailing_commas(mut self, allow: bool) -> Self {
+        self.trailing_commas = Some(allow);
+        self
+    }
+
+    /// Allow the numbers `NaN`, `Infinity` and `-Infinity`
+    pub fn nan_and_infinity(mut self, allow: bool) -> Self {
+        self.nan_and_infinity = Some(allow);
+        self
+    }
+
+    /// Allow integer parts with leading zeros, such as `007`. They are read as decimal.
+    pub fn leading_zeros(mut self, allow: bool) -> Self {
+        self.leading_zeros = allow;
+        self
+    }
+
+    /// Allow control characters such as tabs in strings without escaping them. Line breaks
+    /// still end a string.
+    pub fn control_characters(mut self, allow: bool) -> Self {
+        self.control_characters = Some(allow);
+        self
+    }
+
+    /// Allow strings in single quotes, in which `\'` escapes a quote
+    pub fn single_quotes(mut self, allow: bool) -> Self {
+        self.single_quotes = Some(allow);
+        self
+    }
+
+    pub(crate) fn allows_comments(&self) -> bool {
+        self.comments.unwrap_or(self.json5)
+    }
+
+    pub(crate) fn allows_trailing_commas(&self) -> bool {
+        self.trailing_commas.unwrap_or(self.json5)
+    }
+
+    pub(crate) fn allows_nan_and_infinity(&self) -> bool {
+        self.nan_and_infinity.unwrap_or(self.json5)
+    }
+
+    pub(crate) fn allows_control_characters(&self) -> bool {
+        self.control_characters.unwrap_or(self.json5)
+    }
+
+    pub(crate) fn allows_single_quotes(&self) -> bool {
+        self.single_quotes.unwrap_or(self.json5)
+    }
+}
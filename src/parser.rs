@@ -1,211 +1,830 @@
-use std::collections::HashMap;
+use std::{borrow::Cow, collections::HashSet, marker::PhantomData};
 
 use crate::{
-    errors::{LexerError, ParserError, ParserErrorKind},
+    errors::{HandlerError, LexerError, ParserError, ParserErrorKind},
+    handler::{Events, Handler},
     jsonvalue::{JSONValue, TokenKind},
-    lexer::{Lexer, Token},
+    lexer::{Lexer, Token, TokenSource},
+    number::Number,
+    object::JSONObject,
+    options::{DuplicateKeys, ParserOptions},
+    span::Span,
+    spanned::SpannedValue,
 };
 
-pub fn parse_json(input: &str) -> Result<JSONValue, ParserError> {
+pub use crate::options::DEFAULT_MAX_DEPTH;
+
+/// Parses a complete JSON document. Strings in the result borrow from `input` where possible.
+pub fn parse_json(input: &str) -> Result<JSONValue<'_>, ParserError> {
     let lexer = Lexer::new(input);
     let mut parser = Parser::new(lexer);
     parser.parse()
 }
 
+/// Parses a complete JSON document using the given options. `parse_reader_with`,
+/// `PushParser`, `PullParser` and `parse_json_events_with` give the same values and errors.
+pub fn parse_json_with<'a>(
+    input: &'a str,
+    options: &ParserOptions,
+) -> Result<JSONValue<'a>, ParserError> {
+    let lexer = Lexer::with_options(input, options);
+    let mut parser = Parser::from_tokens(lexer);
+    parser.parse()
+}
+
+/// Like `parse_json`, but with a custom limit on how deeply arrays and objects may nest, see
+/// `ParserOptions::max_depth`
+pub fn parse_json_with_max_depth(
+    input: &str,
+    max_depth: usize,
+) -> Result<JSONValue<'_>, ParserError> {
+    parse_json_with(input, &ParserOptions::new().max_depth(max_depth))
+}
+
+/// Like `parse_json`, but numbers that an `f64` would round are kept as their original text,
+/// see `ParserOptions::arbitrary_precision`
+pub fn parse_json_arbitrary_precision(input: &str) -> Result<JSONValue<'_>, ParserError> {
+    parse_json_with(input, &ParserOptions::new().arbitrary_precision(true))
+}
+
+/// Like `parse_json`, but unpaired surrogates in `\u` escapes become U+FFFD instead of an
+/// error, see `ParserOptions::lossy_surrogates`
+pub fn parse_json_lossy(input: &str) -> Result<JSONValue<'_>, ParserError> {
+    parse_json_with(input, &ParserOptions::new().lossy_surrogates(true))
+}
+
+/// Parses a complete JSON document into a tree where every value and key knows its `Span`
+pub fn parse_json_spanned(input: &str) -> Result<SpannedValue<'_>, ParserError> {
+    parse_json_spanned_with(input, &ParserOptions::default())
+}
+
+/// Parses a complete JSON document into a spanned tree using the given options
+pub fn parse_json_spanned_with<'a>(
+    input: &'a str,
+    options: &ParserOptions,
+) -> Result<SpannedValue<'a>, ParserError> {
+    let lexer = Lexer::with_options(input, options);
+    let mut parser = Parser::from_tokens(lexer);
+    parser.parse_spanned()
+}
+
+/// Parses a document in recovering mode, which carries on after errors to report all of them,
+/// see `Parser::parse_recovering`
+pub fn parse_json_recovering(input: &str) -> Recovered<'_> {
+    parse_json_recovering_with(input, &ParserOptions::default())
+}
+
+/// Parses a document in recovering mode using the given options
+pub fn parse_json_recovering_with<'a>(input: &'a str, options: &ParserOptions) -> Recovered<'a> {
+    let lexer = Lexer::with_options(input, options);
+    let mut parser = Parser::from_tokens(lexer);
+    parser.parse_recovering()
+}
+
+/// What parsing in recovering mode produced
+#[derive(Debug, PartialEq)]
+pub struct Recovered<'a> {
+    /// As much of the document as could be parsed. Values that failed to parse are replaced by
+    /// null, and arrays and objects missing their end hold the members read until then.
+    pub value: JSONValue<'a>,
+    /// Every error found, in source order. Empty when the document is valid.
+    pub errors: Vec<ParserError>,
+}
+
+impl Recovered<'_> {
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
 type PResult<T> = Result<T, ParserError>;
 
-pub struct Parser<I>
+pub struct Parser<'a, I>
 where
-    I: Iterator<Item = Result<Token, LexerError>>,
+    I: Iterator<Item = Result<Token<'a>, LexerError>>,
 {
-    lexer: std::iter::Peekable<I>,
+    lexer: I,
+    peeked: Option<Result<Token<'a>, LexerError>>, // One token of lookahead
+    depth: usize, // Number of arrays and objects we are currently inside
+    options: ParserOptions,
+    errors: Option<Vec<ParserError>>, // Collects errors instead of failing when recovering
 }
 
-impl<I> Parser<I>
+impl<'a, I> Parser<'a, I>
 where
-    I: Iterator<Item = Result<Token, LexerError>>,
+    I: Iterator<Item = Result<Token<'a>, LexerError>>,
 {
+    /// Parses with the default options, see `from_tokens` for tokens lexed with others
     pub fn new(lexer: I) -> Self {
+        Self::with_options(lexer, &ParserOptions::default())
+    }
+
+    fn with_options(lexer: I, options: &ParserOptions) -> Self {
         Self {
-            lexer: lexer.peekable(),
+            lexer,
+            peeked: None,
+            depth: 0,
+            options: options.clone(),
+            errors: None,
+        }
+    }
+
+    /// Parses with the options the tokens are lexed with
+    pub fn from_tokens(lexer: I) -> Self
+    where
+        I: TokenSource<'a>,
+    {
+        let options = lexer.options().clone();
+        Self::with_options(lexer, &options)
+    }
+
+    /// Called with the opening token of an array or object, fails once we are nested too deep
+    fn enter_nested(&mut self, token: &Token<'a>) -> PResult<()> {
+        if self.depth >= self.options.max_depth {
+            return Err(error_at(
+                ParserErrorKind::DepthLimitExceeded(self.options.max_depth),
+                token,
+            ));
         }
+        self.depth += 1;
+        Ok(())
     }
 
-    fn peek(&mut self) -> Result<&Token, ParserError> {
-        match self.lexer.peek() {
+    fn peek(&mut self) -> Result<&Token<'a>, ParserError> {
+        if self.peeked.is_none() {
+            self.peeked = self.lexer.next();
+        }
+        if self.errors.is_some()
+            && let Some(Err(error)) = &self.peeked
+        {
+            let error = error.clone();
+            self.peeked = Some(Ok(self.recover_lexer_error(error)));
+        }
+        match &self.peeked {
             Some(Ok(token)) => Ok(token),
             Some(Err(error)) => Err(ParserError::Lexer(error.clone())),
             None => unreachable!("Lexer should always produce EOF token"),
         }
     }
 
-    fn peek_kind(&mut self) -> Result<&TokenKind, ParserError> {
+    fn peek_kind(&mut self) -> Result<&TokenKind<'a>, ParserError> {
         Ok(&self.peek()?.kind)
     }
 
-    fn advance(&mut self) -> Result<Token, ParserError> {
-        match self.lexer.next() {
+    fn advance(&mut self) -> Result<Token<'a>, ParserError> {
+        match self.peeked.take().or_else(|| self.lexer.next()) {
             Some(Ok(token)) => Ok(token),
+            Some(Err(error)) if self.errors.is_some() => Ok(self.recover_lexer_error(error)),
             Some(Err(error)) => Err(ParserError::Lexer(error)),
             None => unreachable!("Lexer should always produce EOF token"),
         }
     }
 
-    fn advance_kind(&mut self) -> Result<TokenKind, ParserError> {
-        Ok(self.advance()?.kind)
-    }
-
-    fn expect(&mut self, kind: TokenKind) -> Result<Token, ParserError> {
+    fn expect(&mut self, kind: TokenKind<'static>) -> Result<Token<'a>, ParserError> {
         // Uses advance_kind to advance and look at the kind returned
         let k = self.advance()?;
         if k.kind != kind {
-            return Err(ParserError::Parser {
-                kind: ParserErrorKind::ExpectedToken(kind, k.kind),
-                line: k.line,
-                column: k.column,
-            });
+            let kind = ParserErrorKind::ExpectedToken(kind, k.kind.clone().into_owned());
+            return Err(error_at(kind, &k));
         }
         Ok(k)
     }
 
-    fn expect_either(&mut self, kinds: &[TokenKind]) -> Result<Token, ParserError> {
-        // TODO: Pretty hacky but works i guess?
-        let k = self.advance()?;
-        if !kinds.contains(&k.kind) {
-            return Err(ParserError::Parser {
-                kind: ParserErrorKind::ExpectedOneOfTokens(kinds.to_vec(), k.kind),
-                line: k.line,
-                column: k.column,
-            });
+    /// Puts back the token `advance` just returned, so recovery can carry on from it
+    fn unread(&mut self, token: Token<'a>) {
+        debug_assert!(self.peeked.is_none());
+        self.peeked = Some(Ok(token));
+    }
+
+    /// Fails right away normally. When recovering the error is recorded instead, unless one was
+    /// already reported at the same place, as errors tend to cascade from there.
+    fn report(&mut self, error: ParserError) -> PResult<()> {
+        let Some(errors) = self.errors.as_mut() else {
+            return Err(error);
+        };
+        if errors.last().map(|last| last.span().start) != Some(error.span().start) {
+            errors.push(error);
         }
-        Ok(k)
+        Ok(())
     }
 
-    pub fn parse(&mut self) -> PResult<JSONValue> {
-        let value = self.parse_value()?;
-        // After parsing the value, we expect an EOF token
-        self.expect(TokenKind::EOF)?;
+    /// Records a lexer error and stands in a null token for the broken lexeme, which is where a
+    /// value is most likely to have been meant
+    fn recover_lexer_error(&mut self, error: LexerError) -> Token<'a> {
+        let span = error.span;
+        // Only called when recovering, so this never fails
+        let _ = self.report(ParserError::Lexer(error));
+        Token {
+            kind: TokenKind::Null,
+            line: span.start_line,
+            column: span.start_column,
+            span,
+        }
+    }
 
-        Ok(value)
+    /// Skips to the next comma or closing bracket that is not inside an array or object
+    /// starting along the way, which is where a list can carry on after a broken element
+    fn synchronize(&mut self) -> PResult<()> {
+        let mut nesting = 0;
+        loop {
+            match self.peek_kind()? {
+                TokenKind::EOF => break,
+                TokenKind::Comma | TokenKind::RightBracket | TokenKind::RightBrace
+                    if nesting == 0 =>
+                {
+                    break;
+                }
+                TokenKind::LeftBrace | TokenKind::LeftBracket => nesting += 1,
+                TokenKind::RightBrace | TokenKind::RightBracket => nesting -= 1,
+                _ => {}
+            }
+            self.advance()?;
+        }
+        Ok(())
     }
 
-    fn parse_value(&mut self) -> PResult<JSONValue> {
-        // This function should look at the next token and decide which parse_* function to call
-        let token = self.peek()?;
-        match &token.kind {
-            TokenKind::LeftBrace => self.parse_object(),
-            TokenKind::LeftBracket => self.parse_array(),
-            TokenKind::String(_) => {
-                if let TokenKind::String(s) = self.advance_kind()? {
-                    Ok(JSONValue::String(s))
-                } else {
-                    unreachable!()
+    /// After a broken object member, moves on to the next one. Returns false when the object
+    /// ends instead.
+    fn skip_to_next_member(&mut self) -> PResult<bool> {
+        self.synchronize()?;
+        if *self.peek_kind()? == TokenKind::Comma {
+            self.advance()?;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// Called with the error for an array or object nested too deep. When recovering, skips
+    /// the whole thing and leaves a placeholder.
+    fn skip_nested<S: Sink<'a>>(
+        &mut self,
+        sink: &mut S,
+        error: ParserError,
+        open: Span,
+    ) -> Result<S::Value, S::Error> {
+        self.report(error)?;
+        let mut nesting = 1;
+        while nesting > 0 {
+            match self.peek_kind()? {
+                TokenKind::EOF => break,
+                TokenKind::LeftBrace | TokenKind::LeftBracket => nesting += 1,
+                TokenKind::RightBrace | TokenKind::RightBracket => nesting -= 1,
+                _ => {}
+            }
+            self.advance()?;
+        }
+        sink.scalar(JSONValue::Null, open)
+    }
+
+    pub fn parse(&mut self) -> PResult<JSONValue<'a>> {
+        self.parse_document(&mut Tree::new())
+    }
+
+    /// Like `parse`, but every value and key keeps its place in the input
+    pub fn parse_spanned(&mut self) -> PResult<SpannedValue<'a>> {
+        self.parse_document(&mut Tree::new())
+    }
+
+    /// Walks the document without building a value, telling `handler` about each part in
+    /// source order instead. Stops at the first error, or as soon as the handler returns one.
+    pub fn parse_events<H: Handler<'a>>(
+        &mut self,
+        handler: &mut H,
+    ) -> Result<(), HandlerError<H::Error>> {
+        self.parse_document(&mut Events(handler))
+    }
+
+    /// Parses the whole document without stopping at the first error. Broken values are skipped
+    /// up to the next comma or closing bracket and replaced by null, so the rest of the document
+    /// still gets checked.
+    pub fn parse_recovering(&mut self) -> Recovered<'a> {
+        self.errors = Some(Vec::new());
+        let result = self.parse_document(&mut Tree::new());
+        let mut errors = self.errors.take().unwrap_or_default();
+        let value = result.unwrap_or_else(|error| {
+            // Every error is recorded while recovering, this is only a safety net
+            errors.push(error);
+            JSONValue::Null
+        });
+        Recovered { value, errors }
+    }
+
+    /// Parses the next of several values that simply follow each other, such as `{..}{..}`.
+    /// None once only whitespace is left. There is no telling where the next value starts
+    /// after an error, so stop there.
+    pub fn parse_next(&mut self) -> Option<PResult<JSONValue<'a>>> {
+        match self.peek() {
+            Ok(token) if token.kind == TokenKind::EOF => None,
+            Ok(_) => Some(self.parse_value(&mut Tree::new())),
+            Err(error) => Some(Err(error)),
+        }
+    }
+
+    /// Parses the next record of an RFC 7464 JSON text sequence, where each value is preceded
+    /// by RS. The lexer needs `record_separators` set. After an error the rest of the record is
+    /// skipped, so a broken or truncated record does not affect the ones after it.
+    pub fn parse_next_record(&mut self) -> Option<PResult<JSONValue<'a>>> {
+        let result = self.parse_record()?;
+        if result.is_err() {
+            self.skip_record();
+        }
+        Some(result)
+    }
+
+    fn parse_record(&mut self) -> Option<PResult<JSONValue<'a>>> {
+        // A number or literal cut short is still valid JSON, so the RFC has them followed by
+        // whitespace, usually the newline that ends the record
+        let mut started = false;
+        let value_span = loop {
+            let token = match self.peek() {
+                Ok(token) => token,
+                Err(error) => return Some(Err(error)),
+            };
+            match &token.kind {
+                TokenKind::EOF => return None,
+                // Several RS in a row are allowed, the records between them are empty
+                TokenKind::RecordSeparator => {
+                    self.peeked = None;
+                    started = true;
+                }
+                found if !started => {
+                    let kind = ParserErrorKind::ExpectedToken(
+                        TokenKind::RecordSeparator,
+                        found.clone().into_owned(),
+                    );
+                    return Some(Err(error_at(kind, token)));
                 }
+                TokenKind::Number(_) | TokenKind::True | TokenKind::False | TokenKind::Null => {
+                    break Some(token.span);
+                }
+                _ => break None,
             }
-            TokenKind::Number(_) => {
-                if let TokenKind::Number(n) = self.advance_kind()? {
-                    Ok(JSONValue::Number(n))
-                } else {
-                    unreachable!()
+        };
+        let result = self.parse_value(&mut Tree::new()).and_then(|value| {
+            let token = self.peek()?;
+            match token.kind {
+                TokenKind::RecordSeparator | TokenKind::EOF => match value_span {
+                    Some(span) if span.end == token.span.start => Err(ParserError::Parser {
+                        kind: Box::new(ParserErrorKind::TruncatedRecord),
+                        span,
+                    }),
+                    _ => Ok(value),
+                },
+                _ => {
+                    let kind = ParserErrorKind::ExpectedToken(
+                        TokenKind::RecordSeparator,
+                        token.kind.clone().into_owned(),
+                    );
+                    Err(error_at(kind, token))
                 }
             }
-            TokenKind::True => {
-                self.advance_kind()?;
-                Ok(JSONValue::Bool(true))
+        });
+        Some(result)
+    }
+
+    /// Skips tokens and lexer errors alike up to the next RS, or the end of the input
+    fn skip_record(&mut self) {
+        self.depth = 0;
+        while let Some(next) = self.peeked.take().or_else(|| self.lexer.next()) {
+            if let Ok(token) = next
+                && matches!(token.kind, TokenKind::RecordSeparator | TokenKind::EOF)
+            {
+                self.unread(token);
+                break;
+            }
+        }
+    }
+
+    fn parse_document<S: Sink<'a>>(&mut self, sink: &mut S) -> Result<S::Value, S::Error> {
+        let value = self.parse_element(sink)?;
+        // After parsing the value, we expect an EOF token
+        let token = self.peek()?;
+        if token.kind != TokenKind::EOF {
+            let kind =
+                ParserErrorKind::ExpectedToken(TokenKind::EOF, token.kind.clone().into_owned());
+            let error = error_at(kind, token);
+            self.report(error)?;
+        }
+
+        Ok(value)
+    }
+
+    /// Parses a value. When recovering, a value that fails to parse is skipped and replaced by
+    /// a null placeholder.
+    fn parse_element<S: Sink<'a>>(&mut self, sink: &mut S) -> Result<S::Value, S::Error> {
+        if self.errors.is_none() {
+            return self.parse_value(sink);
+        }
+        match self.parse_value(sink) {
+            Err(error) => {
+                let error = S::recoverable(error)?;
+                let span = error.span();
+                self.report(error)?;
+                self.synchronize()?;
+                sink.scalar(JSONValue::Null, span)
             }
-            TokenKind::False => {
-                self.advance_kind()?;
-                Ok(JSONValue::Bool(false))
+            result => result,
+        }
+    }
+
+    fn parse_value<S: Sink<'a>>(&mut self, sink: &mut S) -> Result<S::Value, S::Error> {
+        // This function should look at the next token and decide which parse_* function to call
+        let non_finite = self.options.allows_nan_and_infinity();
+        let token = self.peek()?;
+        match &token.kind {
+            TokenKind::LeftBrace => return self.parse_object(sink),
+            TokenKind::LeftBracket => return self.parse_array(sink),
+            TokenKind::String(_)
+            | TokenKind::Number(_)
+            | TokenKind::True
+            | TokenKind::False
+            | TokenKind::Null => {}
+            TokenKind::Identifier(word) if identifier_value(word.clone(), non_finite).is_some() => {
             }
-            TokenKind::Null => {
-                self.advance_kind()?;
-                Ok(JSONValue::Null)
+            unexpected => {
+                let kind = ParserErrorKind::UnexpectedToken(unexpected.clone().into_owned());
+                return Err(error_at(kind, token).into());
             }
-            unexpected => Err(ParserError::Parser {
-                kind: ParserErrorKind::UnexpectedToken(unexpected.clone()),
-                line: (token.line),
-                column: (token.column),
-            }),
         }
+        let token = self.advance()?;
+        let value = match token.kind {
+            TokenKind::String(s) => JSONValue::String(s),
+            TokenKind::Number(n) => JSONValue::Number(n),
+            TokenKind::True => JSONValue::Bool(true),
+            TokenKind::False => JSONValue::Bool(false),
+            TokenKind::Null => JSONValue::Null,
+            TokenKind::Identifier(word) => identifier_value(word, non_finite).unwrap(),
+            _ => unreachable!("only scalar tokens get here"),
+        };
+        sink.scalar(value, token.span)
     }
 
-    fn parse_object(&mut self) -> PResult<JSONValue> {
+    fn parse_object<S: Sink<'a>>(&mut self, sink: &mut S) -> Result<S::Value, S::Error> {
         //TODO: low cost lookahead and count commas for capacity
         // Exoect the left bracket and consume it, propogating error if another type
-        // Lets initialize a HashMap to hold the key-value pairs
-        let mut map: HashMap<String, JSONValue> = std::collections::HashMap::with_capacity(8);
-        self.expect(TokenKind::LeftBrace)?;
+        let open = self.expect(TokenKind::LeftBrace)?;
+        if let Err(error) = self.enter_nested(&open) {
+            return self.skip_nested(sink, error, open.span);
+        }
+        // Members are kept in source order
+        let mut map = sink.start_object()?;
+        // Where each key was defined, only tracked when duplicates are an error
+        let mut key_positions: Vec<(usize, usize)> = Vec::new();
+        // Indices of members that already hold an array of collected values
+        let mut collected = HashSet::new();
 
         if *self.peek_kind()? != TokenKind::RightBrace {
             loop {
                 // We expect a string
                 let token = self.advance()?;
+                let key_span = token.span;
                 let key = match token.kind {
-                    TokenKind::String(s) => s,
-                    other => {
-                        return Err(ParserError::Parser {
-                            kind: ParserErrorKind::ExpectedToken(
-                                TokenKind::String("".to_string()),
-                                other,
-                            ),
-                            line: token.line,
-                            column: token.column,
-                        });
-                    } //TODO: Imrpove error handling here
+                    // Identifiers only come up in JSON5, where they can be keys
+                    TokenKind::String(s) | TokenKind::Identifier(s) => s,
+                    _ => {
+                        let kind = ParserErrorKind::ExpectedToken(
+                            TokenKind::String(Cow::Borrowed("")),
+                            token.kind.clone().into_owned(),
+                        );
+                        self.report(error_at(kind, &token))?;
+                        // Recovering, carry on from the token we found instead
+                        self.unread(token);
+                        if self.skip_to_next_member()? {
+                            continue;
+                        }
+                        break;
+                    }
                 };
 
+                if self.options.duplicate_keys == DuplicateKeys::Error {
+                    if let Some(i) = S::index_of(&map, &key) {
+                        let (first_line, first_column) = key_positions[i];
+                        self.report(ParserError::Parser {
+                            kind: Box::new(ParserErrorKind::DuplicateKey {
+                                key: key.to_string(),
+                                first_line,
+                                first_column,
+                            }),
+                            span: key_span,
+                        })?;
+                    } else {
+                        key_positions.push((key_span.start_line, key_span.start_column));
+                    }
+                }
+                sink.key(&key)?;
+
                 // After string we expect a colon
-                self.expect(TokenKind::Colon)?;
+                let token = self.advance()?;
+                if token.kind != TokenKind::Colon {
+                    let kind = ParserErrorKind::ExpectedToken(
+                        TokenKind::Colon,
+                        token.kind.clone().into_owned(),
+                    );
+                    self.report(error_at(kind, &token))?;
+                    self.unread(token);
+                    let value = sink.scalar(JSONValue::Null, key_span)?;
+                    sink.insert(
+                        &mut map,
+                        (key, key_span),
+                        value,
+                        self.options.duplicate_keys,
+                        &mut collected,
+                    );
+                    if self.skip_to_next_member()? {
+                        continue;
+                    }
+                    break;
+                }
                 // Next we can pass the value
-                let value = self.parse_value()?;
+                let value = self.parse_element(sink)?;
 
-                map.insert(key, value);
+                sink.insert(
+                    &mut map,
+                    (key, key_span),
+                    value,
+                    self.options.duplicate_keys,
+                    &mut collected,
+                );
                 // next we peek at the next kind, if comma we do nothing, if right brace we break
                 match self.peek_kind()? {
                     TokenKind::Comma => {
                         self.advance()?;
+                        if self.options.allows_trailing_commas()
+                            && *self.peek_kind()? == TokenKind::RightBrace
+                        {
+                            break;
+                        }
                     }
                     TokenKind::RightBrace => break,
-                    other => {
-                        return Err(ParserError::Parser {
-                            kind: ParserErrorKind::ExpectedObjectEndOrComma(other.clone()),
-                            line: self.peek()?.line,
-                            column: self.peek()?.column,
-                        });
+                    _ => {
+                        let token = self.peek()?;
+                        let kind = ParserErrorKind::ExpectedObjectEndOrComma(
+                            token.kind.clone().into_owned(),
+                        );
+                        let error = error_at(kind, token);
+                        self.report(error)?;
+                        if !self.skip_to_next_member()? {
+                            break;
+                        }
                     }
                 }
             }
         }
 
-        self.expect(TokenKind::RightBrace)?;
-        Ok(JSONValue::Object(map))
+        let span = self.close_nested(TokenKind::RightBrace, open.span)?;
+        sink.object(map, span)
     }
 
-    fn parse_array(&mut self) -> PResult<JSONValue> {
+    fn parse_array<S: Sink<'a>>(&mut self, sink: &mut S) -> Result<S::Value, S::Error> {
         //TODO: low cost lookahead and count commas for capacity
         // Consume left bracket
-        self.expect(TokenKind::LeftBracket)?;
-        let mut arr: Vec<JSONValue> = Vec::with_capacity(8);
+        let open = self.expect(TokenKind::LeftBracket)?;
+        if let Err(error) = self.enter_nested(&open) {
+            return self.skip_nested(sink, error, open.span);
+        }
+        sink.start_array()?;
+        let mut arr: Vec<S::Value> = Vec::with_capacity(8);
 
         while *self.peek_kind()? != TokenKind::RightBracket {
+            // When recovering, an array cut short ends where its parent or the input does
+            if self.errors.is_some()
+                && matches!(self.peek_kind()?, TokenKind::RightBrace | TokenKind::EOF)
+            {
+                let token = self.peek()?;
+                let kind = ParserErrorKind::UnexpectedToken(token.kind.clone().into_owned());
+                let error = error_at(kind, token);
+                self.report(error)?;
+                break;
+            }
             // While we dont see the RightBracket, we pass the current JSON value, and expect a
             // comma to be followed
-            arr.push(self.parse_value()?);
-            if *self.peek_kind()? != TokenKind::RightBracket {
-                let token = self.expect_either(&[TokenKind::Comma, TokenKind::RightBracket])?;
-                if *self.peek_kind()? == TokenKind::RightBracket {
-                    return Err(ParserError::Parser {
-                        kind: ParserErrorKind::TrailingComma,
-                        line: token.line,
-                        column: token.column,
-                    });
+            arr.push(self.parse_element(sink)?);
+            match self.peek_kind()? {
+                TokenKind::RightBracket => break,
+                TokenKind::Comma => {
+                    let comma = self.advance()?;
+                    if *self.peek_kind()? == TokenKind::RightBracket
+                        && !self.options.allows_trailing_commas()
+                    {
+                        self.report(error_at(ParserErrorKind::TrailingComma, &comma))?;
+                    }
+                }
+                _ => {
+                    let token = self.peek()?;
+                    let kind = ParserErrorKind::ExpectedOneOfTokens(
+                        vec![TokenKind::Comma, TokenKind::RightBracket],
+                        token.kind.clone().into_owned(),
+                    );
+                    let error = error_at(kind, token);
+                    self.report(error)?;
+                    self.synchronize()?;
+                    if *self.peek_kind()? == TokenKind::Comma {
+                        self.advance()?;
+                    }
                 }
             }
         }
-        // Next token is RightBracket which we can safely consume
-        self.advance()?;
 
-        Ok(JSONValue::Array(arr))
+        let span = self.close_nested(TokenKind::RightBracket, open.span)?;
+        sink.array(arr, span)
+    }
+
+    /// Consumes the closing bracket of an array or object, returning the span of the whole
+    /// thing. When recovering from a missing bracket the span ends where the next token starts.
+    fn close_nested(&mut self, close: TokenKind<'static>, open: Span) -> PResult<Span> {
+        self.depth -= 1;
+        let token = self.advance()?;
+        if token.kind == close {
+            return Ok(open.to(token.span));
+        }
+        let kind = ParserErrorKind::ExpectedToken(close, token.kind.clone().into_owned());
+        self.report(error_at(kind, &token))?;
+        // The token belongs to whatever encloses us
+        let span = open.until(token.span);
+        self.unread(token);
+        Ok(span)
+    }
+}
+
+pub(crate) fn error_at(kind: ParserErrorKind, token: &Token) -> ParserError {
+    ParserError::Parser {
+        kind: Box::new(kind),
+        span: token.span,
+    }
+}
+
+/// The value a JSON5 identifier stands for, if it is one of the words that can be values.
+/// `Infinity` and `NaN` only are with `ParserOptions::nan_and_infinity`.
+pub(crate) fn identifier_value(word: Cow<'_, str>, non_finite: bool) -> Option<JSONValue<'_>> {
+    match word.as_ref() {
+        "true" => Some(JSONValue::Bool(true)),
+        "false" => Some(JSONValue::Bool(false)),
+        "null" => Some(JSONValue::Null),
+        // Parsed like any number, so the text is kept as written
+        "Infinity" | "NaN" if non_finite => {
+            Number::from_lexeme(word, false, false).map(JSONValue::Number)
+        }
+        _ => None,
+    }
+}
+
+/// A tree the parser can build, so the same grammar code produces both plain `JSONValue`s and
+/// `SpannedValue`s
+pub(crate) trait BuildValue<'a>: Sized {
+    /// Holds the members while an object is being parsed
+    type Members;
+
+    /// Strings, numbers, booleans and null, handed over as the matching `JSONValue`
+    fn scalar(value: JSONValue<'a>, span: Span) -> Self;
+    fn array(items: Vec<Self>, span: Span) -> Self;
+    fn new_members() -> Self::Members;
+    fn index_of(object: &Self::Members, key: &str) -> Option<usize>;
+    /// Adds a member, resolving repeated keys according to the policy. `collected` holds the
+    /// indices of members that already hold an array of collected values.
+    fn insert(
+        object: &mut Self::Members,
+        key: (Cow<'a, str>, Span),
+        value: Self,
+        policy: DuplicateKeys,
+        collected: &mut HashSet<usize>,
+    );
+    fn object(object: Self::Members, span: Span) -> Self;
+}
+
+impl<'a> BuildValue<'a> for JSONValue<'a> {
+    type Members = JSONObject<'a>;
+
+    fn scalar(value: JSONValue<'a>, _span: Span) -> Self {
+        value
+    }
+
+    fn array(items: Vec<Self>, _span: Span) -> Self {
+        JSONValue::Array(items)
+    }
+
+    fn new_members() -> Self::Members {
+        JSONObject::with_capacity(8)
+    }
+
+    fn index_of(object: &Self::Members, key: &str) -> Option<usize> {
+        object.index_of(key)
+    }
+
+    fn insert(
+        map: &mut Self::Members,
+        (key, _span): (Cow<'a, str>, Span),
+        value: Self,
+        policy: DuplicateKeys,
+        collected: &mut HashSet<usize>,
+    ) {
+        let Some(i) = map.index_of(&key) else {
+            map.insert(key, value);
+            return;
+        };
+        match policy {
+            // Duplicates were already rejected when reading the key
+            DuplicateKeys::LastWins | DuplicateKeys::Error => {
+                map.insert(key, value);
+            }
+            DuplicateKeys::FirstWins => {}
+            DuplicateKeys::CollectAll => {
+                let existing = map.get_mut(&key).expect("index_of found the key");
+                if collected.insert(i) {
+                    let first = std::mem::replace(existing, JSONValue::Null);
+                    *existing = JSONValue::Array(vec![first, value]);
+                } else if let JSONValue::Array(values) = existing {
+                    values.push(value);
+                }
+            }
+        }
+    }
+
+    fn object(object: Self::Members, _span: Span) -> Self {
+        JSONValue::Object(object)
+    }
+}
+
+/// Where the grammar hands each part of the document as it reads it. `Tree` builds values out of
+/// them, `handler::Events` passes them on to a `Handler` instead.
+pub(crate) trait Sink<'a> {
+    type Value;
+    /// Holds the members while an object is being parsed
+    type Members;
+    type Error: From<ParserError>;
+
+    /// Strings, numbers, booleans and null, handed over as the matching `JSONValue`
+    fn scalar(&mut self, value: JSONValue<'a>, span: Span) -> Result<Self::Value, Self::Error>;
+    fn start_array(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+    fn array(&mut self, items: Vec<Self::Value>, span: Span) -> Result<Self::Value, Self::Error>;
+    fn start_object(&mut self) -> Result<Self::Members, Self::Error>;
+    fn index_of(object: &Self::Members, key: &str) -> Option<usize>;
+    /// Comes before the member's value
+    fn key(&mut self, _key: &Cow<'a, str>) -> Result<(), Self::Error> {
+        Ok(())
+    }
+    /// Same as `BuildValue::insert`
+    fn insert(
+        &mut self,
+        object: &mut Self::Members,
+        key: (Cow<'a, str>, Span),
+        value: Self::Value,
+        policy: DuplicateKeys,
+        collected: &mut HashSet<usize>,
+    );
+    fn object(&mut self, object: Self::Members, span: Span) -> Result<Self::Value, Self::Error>;
+    /// The parser error inside `error`, which recovering mode carries on from
+    fn recoverable(error: Self::Error) -> Result<ParserError, Self::Error>;
+}
+
+/// Builds a tree of `B`s
+pub(crate) struct Tree<B>(PhantomData<B>);
+
+impl<B> Tree<B> {
+    pub(crate) fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<'a, B: BuildValue<'a>> Sink<'a> for Tree<B> {
+    type Value = B;
+    type Members = B::Members;
+    type Error = ParserError;
+
+    fn scalar(&mut self, value: JSONValue<'a>, span: Span) -> PResult<B> {
+        Ok(B::scalar(value, span))
+    }
+
+    fn array(&mut self, items: Vec<B>, span: Span) -> PResult<B> {
+        Ok(B::array(items, span))
+    }
+
+    fn start_object(&mut self) -> PResult<B::Members> {
+        Ok(B::new_members())
+    }
+
+    fn index_of(object: &B::Members, key: &str) -> Option<usize> {
+        B::index_of(object, key)
+    }
+
+    fn insert(
+        &mut self,
+        object: &mut B::Members,
+        key: (Cow<'a, str>, Span),
+        value: B,
+        policy: DuplicateKeys,
+        collected: &mut HashSet<usize>,
+    ) {
+        B::insert(object, key, value, policy, collected);
+    }
+
+    fn object(&mut self, object: B::Members, span: Span) -> PResult<B> {
+        Ok(B::object(object, span))
+    }
+
+    fn recoverable(error: ParserError) -> PResult<ParserError> {
+        Ok(error)
     }
 }
 
@@ -230,8 +849,8 @@ mod tests {
         let json = result.unwrap();
         match json {
             JSONValue::Object(map) => {
-                assert_eq!(map["a"], JSONValue::String("hello".to_string()));
-                assert_eq!(map["b"], JSONValue::Number(123.0));
+                assert_eq!(map["a"], JSONValue::String("hello".into()));
+                assert_eq!(map["b"], JSONValue::Number(123.into()));
                 assert_eq!(map["c"], JSONValue::Bool(true));
                 assert_eq!(map["d"], JSONValue::Bool(false));
                 assert_eq!(map["e"], JSONValue::Null);
@@ -239,4 +858,312 @@ mod tests {
             _ => panic!("Expected JSON object"),
         }
     }
+
+    #[test]
+    fn keeps_object_key_order() {
+        let input = r#"{"z": 1, "a": 2, "m": {"y": 3, "b": 4}}"#;
+        let json = parse_json(input).unwrap();
+        let JSONValue::Object(map) = json else {
+            panic!("Expected JSON object");
+        };
+        assert_eq!(map.keys().collect::<Vec<_>>(), ["z", "a", "m"]);
+        let JSONValue::Object(inner) = &map["m"] else {
+            panic!("Expected JSON object");
+        };
+        assert_eq!(inner.keys().collect::<Vec<_>>(), ["y", "b"]);
+        assert_eq!(crate::to_string(&map["m"]), r#"{"y":3,"b":4}"#);
+    }
+
+    #[test]
+    fn into_owned_outlives_input() {
+        let owned = {
+            let input = String::from(r#"{"key": ["value", "esc\"aped"]}"#);
+            parse_json(&input).unwrap().into_owned()
+        };
+        let JSONValue::Object(map) = owned else {
+            panic!("Expected JSON object");
+        };
+        assert_eq!(
+            map["key"],
+            JSONValue::Array(vec![
+                JSONValue::String("value".into()),
+                JSONValue::String("esc\"aped".into())
+            ])
+        );
+    }
+
+    #[test]
+    fn keeps_large_integers_exact() {
+        let json = parse_json("[9007199254740993, -9007199254740993, 1.5]").unwrap();
+        let JSONValue::Array(arr) = json else {
+            panic!("Expected JSON array");
+        };
+        let JSONValue::Number(n) = &arr[0] else {
+            panic!("Expected number");
+        };
+        assert_eq!(n.as_u64(), Some(9007199254740993));
+        assert_eq!(arr[1], JSONValue::Number((-9007199254740993i64).into()));
+        assert_eq!(arr[2], JSONValue::Number(1.5.into()));
+
+        let input = "[1e400, 3.141592653589793238462643383279]";
+        let options = ParserOptions::new().arbitrary_precision(true);
+        let json = parse_json_with(input, &options).unwrap();
+        assert_eq!(json.to_string(), "[1e400,3.141592653589793238462643383279]");
+    }
+
+    #[test]
+    fn rejects_deep_nesting() {
+        let input = "[".repeat(100_000);
+        let err = parse_json(&input).unwrap_err();
+        assert!(matches!(
+            err,
+            ParserError::Parser { kind, span }
+                if *kind == ParserErrorKind::DepthLimitExceeded(DEFAULT_MAX_DEPTH)
+                    && span.start == DEFAULT_MAX_DEPTH
+        ));
+
+        let input = r#"{"a": [{"b": []}]}"#;
+        assert!(parse_json_with(input, &ParserOptions::new().max_depth(4)).is_ok());
+        let err = parse_json_with(input, &ParserOptions::new().max_depth(3)).unwrap_err();
+        assert!(matches!(
+            &err,
+            ParserError::Parser {
+                kind,
+                span: Span {
+                    start: 13,
+                    end: 14,
+                    ..
+                },
+            } if **kind == ParserErrorKind::DepthLimitExceeded(3)
+        ));
+        assert_eq!(parse_json_with_max_depth(input, 3).unwrap_err(), err);
+    }
+
+    #[test]
+    fn shorthands_match_options() {
+        let input = r#"[1e400, "\ud800"]"#;
+        assert_eq!(
+            parse_json_lossy(input),
+            parse_json_with(input, &ParserOptions::new().lossy_surrogates(true))
+        );
+        let input = "[1e400]";
+        assert_eq!(
+            parse_json_arbitrary_precision(input).unwrap().to_string(),
+            "[1e400]"
+        );
+    }
+
+    #[test]
+    fn applies_duplicate_key_policy() {
+        let input = r#"{"a": 1, "b": 2, "a": [3], "a": 4}"#;
+        let parse = |policy| {
+            parse_json_with(input, &ParserOptions::new().duplicate_keys(policy))
+                .map(|v| v.to_string())
+        };
+
+        assert_eq!(parse(DuplicateKeys::LastWins).unwrap(), r#"{"a":4,"b":2}"#);
+        assert_eq!(parse(DuplicateKeys::FirstWins).unwrap(), r#"{"a":1,"b":2}"#);
+        assert_eq!(
+            parse(DuplicateKeys::CollectAll).unwrap(),
+            r#"{"a":[1,[3],4],"b":2}"#
+        );
+        assert_eq!(
+            parse(DuplicateKeys::Error).unwrap_err(),
+            ParserError::Parser {
+                kind: Box::new(ParserErrorKind::DuplicateKey {
+                    key: "a".to_string(),
+                    first_line: 1,
+                    first_column: 2,
+                }),
+                span: Span {
+                    start: 17,
+                    end: 20,
+                    start_line: 1,
+                    start_column: 18,
+                    end_line: 1,
+                    end_column: 21,
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn errors_work_with_std_error() {
+        fn parse_boxed(input: &str) -> Result<JSONValue<'_>, Box<dyn std::error::Error + '_>> {
+            Ok(parse_json(input)?)
+        }
+
+        let err = parse_boxed("[1,\n  tru]").unwrap_err();
+        assert_eq!(err.to_string(), "2:6: Invalid literal");
+        // The message is the lexer error's own, which is not repeated as the source
+        assert!(err.source().is_none());
+        assert!(matches!(
+            err.downcast_ref::<ParserError>(),
+            Some(ParserError::Lexer(_))
+        ));
+
+        let err = parse_json("[1,\n  tru]").unwrap_err();
+        assert_eq!(
+            (err.line(), err.column(), err.code()),
+            (2, 6, "invalid_literal")
+        );
+        let err = parse_json("[1,]").unwrap_err();
+        assert_eq!(
+            (err.line(), err.column(), err.code()),
+            (1, 3, "trailing_comma")
+        );
+    }
+
+    #[test]
+    fn recovers_from_errors() {
+        let input = "[1, tru, 3, {\"a\" 2, \"b\": 4}, [5 6], \"x\\qy\", 7,]";
+        let recovered = parse_json_recovering(input);
+        let codes: Vec<_> = recovered.errors.iter().map(|e| e.code()).collect();
+        assert_eq!(
+            codes,
+            [
+                "invalid_literal",
+                "expected_token",
+                "expected_one_of_tokens",
+                "invalid_escape_char",
+                "trailing_comma"
+            ]
+        );
+        assert_eq!(
+            recovered.value.to_string(),
+            r#"[1,null,3,{"a":null,"b":4},[5],null,7]"#
+        );
+
+        let valid = r#"{"a": [1, {"b": null}]}"#;
+        let recovered = parse_json_recovering(valid);
+        assert!(recovered.is_ok());
+        assert_eq!(recovered.value, parse_json(valid).unwrap());
+    }
+
+    #[test]
+    fn recovers_from_unclosed_input() {
+        let recovered = parse_json_recovering("{\"a\": [1, 2\n");
+        assert_eq!(recovered.value.to_string(), r#"{"a":[1,2]}"#);
+        // The missing ']' and '}' are both reported at the end of the input, only once
+        assert_eq!(recovered.errors.len(), 1);
+        assert_eq!(recovered.errors[0].code(), "expected_one_of_tokens");
+        assert_eq!(recovered.errors[0].line(), 2);
+
+        let recovered = parse_json_recovering("[1, 2]]  [");
+        assert_eq!(recovered.value.to_string(), "[1,2]");
+        assert_eq!(recovered.errors.len(), 1);
+
+        // Whatever is nested too deep is skipped as a whole
+        let options = ParserOptions::new().max_depth(2);
+        let recovered = parse_json_recovering_with("[1, [[2, [3]]], 4]", &options);
+        assert_eq!(recovered.value.to_string(), "[1,[null],4]");
+        assert_eq!(recovered.errors[0].code(), "depth_limit_exceeded");
+        assert_eq!(recovered.errors.len(), 1);
+    }
+
+    #[test]
+    fn parses_comments_when_enabled() {
+        let input =
+            "// settings\n{\n  \"a\": 1, // one\n  /* \"b\": 2, */ \"c\": [/**/]\n}\n// end";
+        assert_eq!(parse_json(input).unwrap_err().code(), "unexpected_char");
+        let options = ParserOptions::new().comments(true);
+        let json = parse_json_with(input, &options).unwrap();
+        assert_eq!(json.to_string(), r#"{"a":1,"c":[]}"#);
+
+        let err = parse_json_with("[1] /* never closed\n", &options).unwrap_err();
+        assert_eq!(err.code(), "unterminated_comment");
+        assert_eq!(err.span().start, 4);
+    }
+
+    #[test]
+    fn parses_json5() {
+        let input = r#"// JSON5 example
+{
+  unquoted: 'and you can quote me on that',
+  singleQuotes: 'I can use "double quotes" here',
+  lineBreaks: "Look, Mom! \
+No \\n's!",
+  hexadecimal: 0xdecaf,
+  leadingDecimalPoint: .8675309, andTrailing: 8675309.,
+  positiveSign: +1,
+  trailingComma: 'in objects', andIn: ['arrays',],
+  "backwardsCompatible": "with JSON",
+  null: [Infinity, -Infinity, true, null],
+}"#;
+        assert!(parse_json(input).is_err());
+        let options = ParserOptions::new().json5(true);
+        let json = parse_json_with(input, &options).unwrap();
+        assert_eq!(
+            json.to_string(),
+            "{\"unquoted\":\"and you can quote me on that\",\
+             \"singleQuotes\":\"I can use \\\"double quotes\\\" here\",\
+             \"lineBreaks\":\"Look, Mom! No \\\\n's!\",\
+             \"hexadecimal\":912559,\
+             \"leadingDecimalPoint\":0.8675309,\"andTrailing\":8675309.0,\
+             \"positiveSign\":1,\
+             \"trailingComma\":\"in objects\",\"andIn\":[\"arrays\"],\
+             \"backwardsCompatible\":\"with JSON\",\
+             \"null\":[1e999,-1e999,true,null]}"
+        );
+
+        // Still only one trailing comma, and words that are no values stay errors
+        for input in ["[1,,]", "{a: 1,,}", "[undefined]", "{'a' 1}"] {
+            assert!(parse_json_with(input, &options).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn applies_leniency_flags() {
+        let lenient = ParserOptions::new;
+        let cases = [
+            ("[1, 2,]", lenient().trailing_commas(true), "[1,2]"),
+            (
+                r#"{"a": 1,}"#,
+                lenient().trailing_commas(true),
+                r#"{"a":1}"#,
+            ),
+            (
+                "[NaN, -Infinity]",
+                lenient().nan_and_infinity(true),
+                "[null,-1e999]",
+            ),
+            ("[007, -01.5]", lenient().leading_zeros(true), "[7,-1.5]"),
+            ("\"a\tb\"", lenient().control_characters(true), r#""a\tb""#),
+            (
+                r#"['it\'s', "it's"]"#,
+                lenient().single_quotes(true),
+                r#"["it's","it's"]"#,
+            ),
+            // Flags can be taken back out of JSON5
+            (
+                "{a: 1}",
+                lenient().json5(true).trailing_commas(false),
+                r#"{"a":1}"#,
+            ),
+        ];
+        for (input, options, expected) in cases {
+            assert!(parse_json(input).is_err(), "{}", input);
+            let json = parse_json_with(input, &options).unwrap();
+            assert_eq!(json.to_string(), expected);
+        }
+
+        // Each flag only allows its own thing
+        let options = ParserOptions::new().trailing_commas(true);
+        assert!(parse_json_with("[1,,]", &options).is_err());
+        assert!(parse_json_with("['a']", &options).is_err());
+        let options = ParserOptions::new().json5(true).trailing_commas(false);
+        assert!(parse_json_with("[1,]", &options).is_err());
+        let options = ParserOptions::new().trailing_commas(false).json5(true);
+        assert!(parse_json_with("[1,]", &options).is_err());
+        // Turning JSON5 back off turns off what it turned on, but not what was set on its own
+        let options = ParserOptions::new().json5(true).json5(false);
+        assert_eq!(options, ParserOptions::new());
+        let options = ParserOptions::new().comments(true).json5(true).json5(false);
+        assert!(parse_json_with("[1, /* */ 2]", &options).is_ok());
+        assert!(parse_json_with("['a']", &options).is_err());
+        let options = ParserOptions::new().nan_and_infinity(true);
+        assert!(parse_json_with("[Infinit]", &options).is_err());
+        let options = ParserOptions::new().control_characters(true);
+        assert!(parse_json_with("\"a\nb\"", &options).is_err());
+    }
 }
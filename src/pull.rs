@@ -0,0 +1,582 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+};
+
+use crate::{
+    errors::{LexerError, ParserError, ParserErrorKind},
+    jsonvalue::{JSONValue, TokenKind},
+    lexer::{Token, TokenSource, Tokens},
+    options::{DuplicateKeys, ParserOptions},
+    parser::{BuildValue, error_at, identifier_value},
+    span::Span,
+};
+
+/// One part of a document, as `PullParser` hands them out
+#[derive(Debug, PartialEq)]
+pub enum Event<'a> {
+    BeginObject,
+    /// Comes before the member's value. Keys are passed on as they come, repeated ones too,
+    /// unless `ParserOptions::duplicate_keys` makes them an error.
+    Key(Cow<'a, str>),
+    EndObject,
+    BeginArray,
+    EndArray,
+    /// A string, number, bool or null, never an array or object
+    Value(JSONValue<'a>),
+}
+
+/// Parses a document one event at a time, for decoders that drive the parsing themselves
+/// rather than being called back like a `Handler`. Nothing is built, so memory use only
+/// depends on how deeply the document nests.
+///
+/// Every event comes with its span. Begin events span the opening bracket, end events the
+/// whole array or object. Once a call fails the parser is done: later calls fail the same, and
+/// as an iterator it yields nothing more.
+pub struct PullParser<'a, I = Tokens<'a>>
+where
+    I: Iterator<Item = Result<Token<'a>, LexerError>>,
+{
+    lexer: I,
+    peeked: Option<Result<Token<'a>, LexerError>>, // One token of lookahead, for skip_value
+    options: ParserOptions,
+    stack: Vec<Frame<'a>>, // The arrays and objects we are inside, innermost last
+    expect: Expect,
+    failed: Option<ParserError>, // Returned again by every call after the first error
+}
+
+/// An array or object we are inside, along with the span of its opening bracket
+enum Frame<'a> {
+    Array(Span),
+    Object {
+        open: Span,
+        // Where each key was defined, only tracked when duplicates are an error
+        key_positions: HashMap<Cow<'a, str>, (usize, usize)>,
+    },
+}
+
+/// What the next token may be. Mirrors where `Parser` is in its grammar functions.
+enum Expect {
+    /// A value, at the start of the document
+    Value,
+    /// A value or `]`, right after `[`
+    FirstElement,
+    /// A value after a comma in an array, or `]` when the comma may trail
+    ElementAfterComma(Span),
+    /// A comma or `]` after an element
+    ElementEnd,
+    /// A key or `}`, right after `{`
+    FirstKey,
+    /// A key after a comma in an object, or `}` when the comma may trail
+    Key,
+    /// The colon after a key, and the value after it
+    Colon,
+    /// A comma or `}` after a member
+    MemberEnd,
+    /// Nothing but the end of the input after the value
+    Eof,
+    /// The end of the input has been reached
+    Done,
+}
+
+type Item<'a> = (Event<'a>, Span);
+
+impl<'a> PullParser<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Self::with_options(input, &ParserOptions::default())
+    }
+
+    pub fn with_options(input: &'a str, options: &ParserOptions) -> Self {
+        Self::from_tokens(Tokens::new(input, options))
+    }
+}
+
+impl<'a, I> PullParser<'a, I>
+where
+    I: Iterator<Item = Result<Token<'a>, LexerError>>,
+{
+    /// Pulls from any token source, such as `reader::ReadLexer`, with the options it lexes with.
+    /// A source that has no token ready yet returns None, `next_event` then returns None until
+    /// it has, see `push::PushParser`.
+    pub(crate) fn from_tokens(lexer: I) -> Self
+    where
+        I: TokenSource<'a>,
+    {
+        Self {
+            options: lexer.options().clone(),
+            lexer,
+            peeked: None,
+            stack: Vec::new(),
+            expect: Expect::Value,
+            failed: None,
+        }
+    }
+
+    /// The next event along with its span, None once the document has ended
+    pub fn next_event(&mut self) -> Result<Option<Item<'a>>, ParserError> {
+        if let Some(error) = &self.failed {
+            return Err(error.clone());
+        }
+        while !matches!(self.expect, Expect::Done) {
+            let result = match self.next_token() {
+                Ok(Some(token)) => self.token(token),
+                Ok(None) => return Ok(None),
+                Err(error) => Err(error),
+            };
+            match result {
+                Ok(Some(item)) => return Ok(Some(item)),
+                Ok(None) => {}
+                Err(error) => {
+                    self.failed = Some(error.clone());
+                    return Err(error);
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Skips the value the next event would start, arrays and objects as a whole, and returns
+    /// its span. The value is still checked, but none of it is built.
+    ///
+    /// Call it at the start of the document, right after a `Key` or anywhere inside an array.
+    /// Anywhere else, and where an array ends instead, nothing is skipped and None is returned.
+    pub fn skip_value(&mut self) -> Result<Option<Span>, ParserError> {
+        if !self.at_value()? {
+            return Ok(None);
+        }
+        let depth = self.stack.len();
+        loop {
+            match self.next_event()? {
+                Some((_, span)) if self.stack.len() == depth => return Ok(Some(span)),
+                Some(_) => {}
+                None => unreachable!("the document cannot end inside a value"),
+            }
+        }
+    }
+
+    /// Reads the value the next event would start into a `JSONValue`, arrays and objects as a
+    /// whole, with repeated keys resolved by `ParserOptions::duplicate_keys`. Can be called
+    /// where `skip_value` can, and likewise reads nothing and returns None anywhere else.
+    pub fn read_value(&mut self) -> Result<Option<JSONValue<'a>>, ParserError> {
+        if !self.at_value()? {
+            return Ok(None);
+        }
+        self.build_value().map(Some)
+    }
+
+    fn build_value<B: BuildValue<'a>>(&mut self) -> Result<B, ParserError> {
+        let mut builder = Builder::new(self.options.duplicate_keys);
+        loop {
+            let Some((event, span)) = self.next_event()? else {
+                unreachable!("the document cannot end inside a value")
+            };
+            if let Some(value) = builder.push(event, span) {
+                return Ok(value);
+            }
+        }
+    }
+
+    /// Whether the next event starts a value, see `skip_value`. Between array elements this
+    /// takes the comma before the next one.
+    fn at_value(&mut self) -> Result<bool, ParserError> {
+        if let Some(error) = &self.failed {
+            return Err(error.clone());
+        }
+        if let Expect::ElementEnd = self.expect
+            && *self.peek_kind()? == TokenKind::Comma
+            && let Some(comma) = self.next_token()?
+        {
+            self.token(comma)?;
+        }
+        match self.expect {
+            Expect::Value | Expect::Colon => Ok(true),
+            Expect::FirstElement | Expect::ElementAfterComma(_) => {
+                Ok(*self.peek_kind()? != TokenKind::RightBracket)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// How many arrays and objects the parser is inside
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// The token source, for handing it more input
+    pub(crate) fn tokens_mut(&mut self) -> &mut I {
+        &mut self.lexer
+    }
+
+    /// None when the token source has no token ready yet
+    fn next_token(&mut self) -> Result<Option<Token<'a>>, ParserError> {
+        match self.peeked.take().or_else(|| self.lexer.next()) {
+            Some(result) => Ok(Some(result?)),
+            None => Ok(None),
+        }
+    }
+
+    fn peek_kind(&mut self) -> Result<&TokenKind<'a>, ParserError> {
+        if self.peeked.is_none() {
+            self.peeked = self.lexer.next();
+        }
+        match &self.peeked {
+            Some(Ok(token)) => Ok(&token.kind),
+            Some(Err(error)) => Err(ParserError::Lexer(error.clone())),
+            None => unreachable!("skip_value and read_value need a source that never waits"),
+        }
+    }
+
+    /// Takes the next token, the way `Parser` would at the same place in the grammar. Commas
+    /// and colons give no event.
+    fn token(&mut self, token: Token<'a>) -> Result<Option<Item<'a>>, ParserError> {
+        match std::mem::replace(&mut self.expect, Expect::Value) {
+            Expect::Value => self.value(token),
+            Expect::FirstElement if token.kind == TokenKind::RightBracket => self.close(token),
+            Expect::ElementAfterComma(comma) if token.kind == TokenKind::RightBracket => {
+                if !self.options.allows_trailing_commas() {
+                    let kind = ParserErrorKind::TrailingComma;
+                    return Err(ParserError::Parser {
+                        kind: Box::new(kind),
+                        span: comma,
+                    });
+                }
+                self.close(token)
+            }
+            Expect::FirstElement | Expect::ElementAfterComma(_) => self.value(token),
+            Expect::ElementEnd => match token.kind {
+                TokenKind::RightBracket => self.close(token),
+                TokenKind::Comma => {
+                    self.expect = Expect::ElementAfterComma(token.span);
+                    Ok(None)
+                }
+                found => {
+                    let kind = ParserErrorKind::ExpectedOneOfTokens(
+                        vec![TokenKind::Comma, TokenKind::RightBracket],
+                        found.into_owned(),
+                    );
+                    Err(ParserError::Parser {
+                        kind: Box::new(kind),
+                        span: token.span,
+                    })
+                }
+            },
+            Expect::FirstKey if token.kind == TokenKind::RightBrace => self.close(token),
+            Expect::Key
+                if self.options.allows_trailing_commas() && token.kind == TokenKind::RightBrace =>
+            {
+                self.close(token)
+            }
+            Expect::FirstKey | Expect::Key => self.key(token),
+            Expect::Colon if token.kind == TokenKind::Colon => Ok(None),
+            Expect::Colon => {
+                let kind = ParserErrorKind::ExpectedToken(
+                    TokenKind::Colon,
+                    token.kind.clone().into_owned(),
+                );
+                Err(error_at(kind, &token))
+            }
+            Expect::MemberEnd => match token.kind {
+                TokenKind::RightBrace => self.close(token),
+                TokenKind::Comma => {
+                    self.expect = Expect::Key;
+                    Ok(None)
+                }
+                found => {
+                    let kind = ParserErrorKind::ExpectedObjectEndOrComma(found.into_owned());
+                    Err(ParserError::Parser {
+                        kind: Box::new(kind),
+                        span: token.span,
+                    })
+                }
+            },
+            Expect::Eof if token.kind == TokenKind::EOF => {
+                self.expect = Expect::Done;
+                Ok(None)
+            }
+            Expect::Eof => {
+                let kind =
+                    ParserErrorKind::ExpectedToken(TokenKind::EOF, token.kind.clone().into_owned());
+                Err(error_at(kind, &token))
+            }
+            Expect::Done => unreachable!("no tokens are read once done"),
+        }
+    }
+
+    fn value(&mut self, token: Token<'a>) -> Result<Option<Item<'a>>, ParserError> {
+        let non_finite = self.options.allows_nan_and_infinity();
+        let value = match token.kind {
+            TokenKind::LeftBrace | TokenKind::LeftBracket => {
+                if self.stack.len() >= self.options.max_depth {
+                    let kind = ParserErrorKind::DepthLimitExceeded(self.options.max_depth);
+                    return Err(error_at(kind, &token));
+                }
+                let event = if token.kind == TokenKind::LeftBrace {
+                    self.stack.push(Frame::Object {
+                        open: token.span,
+                        key_positions: HashMap::new(),
+                    });
+                    self.expect = Expect::FirstKey;
+                    Event::BeginObject
+                } else {
+                    self.stack.push(Frame::Array(token.span));
+                    self.expect = Expect::FirstElement;
+                    Event::BeginArray
+                };
+                return Ok(Some((event, token.span)));
+            }
+            TokenKind::String(s) => JSONValue::String(s),
+            TokenKind::Number(n) => JSONValue::Number(n),
+            TokenKind::True => JSONValue::Bool(true),
+            TokenKind::False => JSONValue::Bool(false),
+            TokenKind::Null => JSONValue::Null,
+            TokenKind::Identifier(word) => match identifier_value(word.clone(), non_finite) {
+                Some(value) => value,
+                None => {
+                    let found = TokenKind::Identifier(word).into_owned();
+                    return Err(ParserError::Parser {
+                        kind: Box::new(ParserErrorKind::UnexpectedToken(found)),
+                        span: token.span,
+                    });
+                }
+            },
+            unexpected => {
+                return Err(ParserError::Parser {
+                    kind: Box::new(ParserErrorKind::UnexpectedToken(unexpected.into_owned())),
+                    span: token.span,
+                });
+            }
+        };
+        self.complete();
+        Ok(Some((Event::Value(value), token.span)))
+    }
+
+    fn key(&mut self, token: Token<'a>) -> Result<Option<Item<'a>>, ParserError> {
+        let Some(Frame::Object { key_positions, .. }) = self.stack.last_mut() else {
+            unreachable!("keys are only expected inside objects")
+        };
+        let name = match token.kind {
+            TokenKind::String(s) | TokenKind::Identifier(s) => s,
+            found => {
+                let kind = ParserErrorKind::ExpectedToken(
+                    TokenKind::String(Cow::Borrowed("")),
+                    found.into_owned(),
+                );
+                return Err(ParserError::Parser {
+                    kind: Box::new(kind),
+                    span: token.span,
+                });
+            }
+        };
+        let span = token.span;
+        if self.options.duplicate_keys == DuplicateKeys::Error {
+            if let Some(&(first_line, first_column)) = key_positions.get(&name) {
+                return Err(ParserError::Parser {
+                    kind: Box::new(ParserErrorKind::DuplicateKey {
+                        key: name.to_string(),
+                        first_line,
+                        first_column,
+                    }),
+                    span,
+                });
+            }
+            key_positions.insert(name.clone(), (span.start_line, span.start_column));
+        }
+        self.expect = Expect::Colon;
+        Ok(Some((Event::Key(name), span)))
+    }
+
+    /// Ends the innermost array or object at its closing bracket
+    fn close(&mut self, token: Token<'a>) -> Result<Option<Item<'a>>, ParserError> {
+        let (event, open) = match self.stack.pop() {
+            Some(Frame::Array(open)) => (Event::EndArray, open),
+            Some(Frame::Object { open, .. }) => (Event::EndObject, open),
+            None => unreachable!("only expected inside an array or object"),
+        };
+        self.complete();
+        Ok(Some((event, open.to(token.span))))
+    }
+
+    /// Moves on past a finished value, to whatever may follow it in the enclosing value
+    fn complete(&mut self) {
+        self.expect = match self.stack.last() {
+            None => Expect::Eof,
+            Some(Frame::Array(_)) => Expect::ElementEnd,
+            Some(Frame::Object { .. }) => Expect::MemberEnd,
+        };
+    }
+}
+
+/// Members of an array or object that `Builder` is building
+enum Partial<'a, B: BuildValue<'a>> {
+    Array(Vec<B>),
+    Object {
+        members: B::Members,
+        key: Option<(Cow<'a, str>, Span)>, // Read, waiting for its value
+        collected: HashSet<usize>,
+    },
+}
+
+/// Builds a value out of the events of a parser, one event at a time, for `read_value` and
+/// `push::PushParser`
+pub(crate) struct Builder<'a, B: BuildValue<'a>> {
+    open: Vec<Partial<'a, B>>, // The arrays and objects being built, innermost last
+    policy: DuplicateKeys,
+}
+
+impl<'a, B: BuildValue<'a>> Builder<'a, B> {
+    pub(crate) fn new(policy: DuplicateKeys) -> Self {
+        Self {
+            open: Vec::new(),
+            policy,
+        }
+    }
+
+    /// Takes the next event, and returns the value once it is complete
+    pub(crate) fn push(&mut self, event: Event<'a>, span: Span) -> Option<B> {
+        let value = match event {
+            Event::BeginArray => {
+                self.open.push(Partial::Array(Vec::with_capacity(8)));
+                return None;
+            }
+            Event::BeginObject => {
+                self.open.push(Partial::Object {
+                    members: B::new_members(),
+                    key: None,
+                    collected: HashSet::new(),
+                });
+                return None;
+            }
+            Event::Key(name) => {
+                if let Some(Partial::Object { key, .. }) = self.open.last_mut() {
+                    *key = Some((name, span));
+                }
+                return None;
+            }
+            Event::Value(value) => B::scalar(value, span),
+            Event::EndArray | Event::EndObject => match self.open.pop() {
+                Some(Partial::Array(items)) => B::array(items, span),
+                Some(Partial::Object { members, .. }) => B::object(members, span),
+                None => unreachable!("values end where they began"),
+            },
+        };
+        match self.open.last_mut() {
+            None => return Some(value),
+            Some(Partial::Array(items)) => items.push(value),
+            Some(Partial::Object {
+                members,
+                key,
+                collected,
+            }) => {
+                let key = key.take().expect("values in objects follow a key");
+                B::insert(members, key, value, self.policy, collected);
+            }
+        }
+        None
+    }
+}
+
+impl<'a, I> Iterator for PullParser<'a, I>
+where
+    I: Iterator<Item = Result<Token<'a>, LexerError>>,
+{
+    type Item = Result<Item<'a>, ParserError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed.is_some() {
+            return None;
+        }
+        self.next_event().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn events(input: &str) -> Vec<String> {
+        PullParser::new(input)
+            .map(|result| match result {
+                Ok((Event::Key(key), _)) => format!("{}:", key),
+                Ok((Event::Value(value), _)) => value.to_string(),
+                Ok((event, _)) => format!("{:?}", event),
+                Err(e) => e.code().to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_events_in_source_order() {
+        assert_eq!(
+            events(r#"{"a": [1, {"b": true}], "c": "x", "a": null}"#).join(" "),
+            "BeginObject a: BeginArray 1 BeginObject b: true EndObject EndArray c: \"x\" a: null \
+             EndObject"
+        );
+        assert_eq!(events(" 12 "), ["12"]);
+        assert_eq!(
+            events("[1 2]"),
+            ["BeginArray", "1", "expected_one_of_tokens"]
+        );
+
+        let input = r#"{"a": [1]}"#;
+        let spans: Vec<_> = PullParser::new(input)
+            .map(|result| {
+                let span = result.unwrap().1;
+                &input[span.start..span.end]
+            })
+            .collect();
+        assert_eq!(spans, ["{", "\"a\"", "[", "1", "[1]", input]);
+    }
+
+    #[test]
+    fn test_skip_value() {
+        let input = r#"{"skip": {"deep": [1, [2]], "x": "y"}, "keep": [[], 3]}"#;
+        let mut parser = PullParser::new(input);
+        assert_eq!(parser.next_event().unwrap().unwrap().0, Event::BeginObject);
+        assert_eq!(parser.skip_value().unwrap(), None);
+        assert_eq!(
+            parser.next_event().unwrap().unwrap().0,
+            Event::Key("skip".into())
+        );
+        let span = parser.skip_value().unwrap().unwrap();
+        assert_eq!(
+            &input[span.start..span.end],
+            r#"{"deep": [1, [2]], "x": "y"}"#
+        );
+        assert_eq!(parser.depth(), 1);
+
+        assert_eq!(
+            parser.next_event().unwrap().unwrap().0,
+            Event::Key("keep".into())
+        );
+        assert_eq!(parser.next_event().unwrap().unwrap().0, Event::BeginArray);
+        let span = parser.skip_value().unwrap().unwrap();
+        assert_eq!(&input[span.start..span.end], "[]");
+        let span = parser.skip_value().unwrap().unwrap();
+        assert_eq!(&input[span.start..span.end], "3");
+        assert_eq!(parser.skip_value().unwrap(), None);
+        assert_eq!(parser.next_event().unwrap().unwrap().0, Event::EndArray);
+        assert_eq!(parser.next_event().unwrap().unwrap().0, Event::EndObject);
+        assert_eq!(parser.next_event().unwrap(), None);
+
+        // Skipped values are still checked
+        let mut parser = PullParser::new("[[1,], 2]");
+        parser.next_event().unwrap();
+        let err = parser.skip_value().unwrap_err();
+        assert_eq!(err.code(), "trailing_comma");
+        assert_eq!(parser.next_event().unwrap_err(), err);
+    }
+
+    #[test]
+    fn test_read_value() {
+        let options = ParserOptions::new().duplicate_keys(DuplicateKeys::CollectAll);
+        let input = r#"[{"a": 1, "b": [], "a": {"c": null}}, "x"]"#;
+        let mut parser = PullParser::with_options(input, &options);
+        assert_eq!(parser.next_event().unwrap().unwrap().0, Event::BeginArray);
+        let value = parser.read_value().unwrap().unwrap();
+        assert_eq!(value.to_string(), r#"{"a":[1,{"c":null}],"b":[]}"#);
+        let value = parser.read_value().unwrap().unwrap();
+        assert_eq!(value, JSONValue::String("x".into()));
+        assert_eq!(parser.read_value().unwrap(), None);
+        assert_eq!(parser.next_event().unwrap().unwrap().0, Event::EndArray);
+    }
+}
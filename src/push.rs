@@ -0,0 +1,136 @@
+use crate::{
+    errors::{LexerError, ParserError, ReadError},
+    jsonvalue::JSONValue,
+    lexer::{Token, TokenSource},
+    options::ParserOptions,
+    pull::{Builder, PullParser},
+    reader::Window,
+};
+
+/// Parses a document that arrives in pieces, such as a request body read off a socket. Hand
+/// each piece to `feed` as it arrives and call `finish` at the end.
+///
+/// Tokens are parsed as soon as the bytes after them show they are complete, so only the value
+/// built so far and the end of the last piece are held, and errors come up without waiting for
+/// `finish`. Input that is not valid UTF-8 gives `ReadError::Io`.
+pub struct PushParser {
+    parser: PullParser<'static, WindowTokens>,
+    builder: Builder<'static, JSONValue<'static>>,
+    root: Option<JSONValue<'static>>,
+    failed: Option<ReadError>, // Returned again by every call after the first error
+}
+
+/// The tokens of the input fed so far, none once the window runs out of complete ones
+struct WindowTokens {
+    window: Window,
+    eof: bool, // Whether all of the input has been fed
+}
+
+impl Iterator for WindowTokens {
+    type Item = Result<Token<'static>, LexerError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.window.next_token(self.eof)
+    }
+}
+
+impl TokenSource<'static> for WindowTokens {
+    fn options(&self) -> &ParserOptions {
+        self.window.options()
+    }
+}
+
+impl Default for PushParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PushParser {
+    pub fn new() -> Self {
+        Self::with_options(&ParserOptions::default())
+    }
+
+    pub fn with_options(options: &ParserOptions) -> Self {
+        let tokens = WindowTokens {
+            window: Window::new(options),
+            eof: false,
+        };
+        Self {
+            parser: PullParser::from_tokens(tokens),
+            builder: Builder::new(options.duplicate_keys),
+            root: None,
+            failed: None,
+        }
+    }
+
+    /// Parses the next piece of the input. Pieces can end anywhere, in the middle of a token or
+    /// even of a char. Errors stick, as with `PullParser`.
+    pub fn feed(&mut self, bytes: &[u8]) -> Result<(), ReadError> {
+        if let Some(error) = &self.failed {
+            return Err(error.clone());
+        }
+        let result = match self.parser.tokens_mut().window.push(bytes) {
+            Ok(()) => self.parse_events().map_err(ReadError::from),
+            Err(error) => Err(error.into()),
+        };
+        self.fail_on(result)
+    }
+
+    /// Parses what is left once all of the input has been fed, and returns the value
+    pub fn finish(mut self) -> Result<JSONValue<'static>, ReadError> {
+        if let Some(error) = self.failed {
+            return Err(error);
+        }
+        let tokens = self.parser.tokens_mut();
+        tokens.window.finish()?;
+        tokens.eof = true;
+        self.parse_events()?;
+        // The end of the input either completes the value or fails
+        Ok(self.root.take().expect("EOF accepted without a value"))
+    }
+
+    fn fail_on(&mut self, result: Result<(), ReadError>) -> Result<(), ReadError> {
+        if let Err(error) = &result {
+            self.failed = Some(error.clone());
+        }
+        result
+    }
+
+    /// Parses every event whose tokens are complete
+    fn parse_events(&mut self) -> Result<(), ParserError> {
+        while let Some((event, span)) = self.parser.next_event()? {
+            if let Some(value) = self.builder.push(event, span) {
+                self.root = Some(value);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_errors_come_up_early() {
+        let mut parser = PushParser::new();
+        parser.feed(b"[1, 2").unwrap();
+        let err = parser.feed(b"] 3, 4, 5, 6, 7, 8, 9, 10").unwrap_err();
+        assert!(matches!(&err, ReadError::Parse(e) if e.code() == "expected_token"));
+        // And stick
+        assert!(parser.feed(b"").is_err());
+        assert!(parser.finish().is_err());
+
+        let mut parser = PushParser::new();
+        parser.feed(b"\"\xc3").unwrap();
+        parser.feed(b"\xa9\"").unwrap();
+        assert_eq!(parser.finish().unwrap(), JSONValue::String("é".into()));
+
+        let mut parser = PushParser::new();
+        assert!(matches!(parser.feed(b"[\"\xff\"]"), Err(ReadError::Io(_))));
+        let mut parser = PushParser::new();
+        parser.feed(b"\"\xc3").unwrap();
+        assert!(matches!(parser.finish(), Err(ReadError::Io(_))));
+    }
+}
@@ -0,0 +1,400 @@
+use std::{
+    io::{self, Read},
+    sync::Arc,
+};
+
+use crate::{
+    errors::{LexerError, LexerErrorKinds, ReadError},
+    jsonvalue::{JSONValue, TokenKind},
+    lexer::{Lexer, Token, TokenSource},
+    options::ParserOptions,
+    parser::Parser,
+    span::Span,
+};
+
+/// How much is read from the reader at a time
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Parses a JSON document read from `reader`, see `parse_reader_with`
+pub fn parse_reader<R: Read>(reader: R) -> Result<JSONValue<'static>, ReadError> {
+    parse_reader_with(reader, &ParserOptions::default())
+}
+
+/// Parses a JSON document while it is being read, without reading all of it into memory first.
+/// Besides the value being built only the current token and one chunk of input are held, so
+/// files larger than memory and pipes work too. The reader is read in large chunks already,
+/// wrapping it in a `BufReader` gains nothing.
+///
+/// Reading errors and input that is not valid UTF-8 give `ReadError::Io`.
+pub fn parse_reader_with<R: Read>(
+    reader: R,
+    options: &ParserOptions,
+) -> Result<JSONValue<'static>, ReadError> {
+    let lexer = ReadLexer::new(reader, options);
+    Ok(Parser::from_tokens(lexer).parse()?)
+}
+
+/// Lexes input that arrives in pieces. The window holds what has arrived but has not been lexed
+/// yet, lexed tokens are dropped from its front before more is added.
+///
+/// A token is only lexed once it is known to be complete, which a scan over each new byte tells.
+/// The scan keeps its state between pieces, so a long string arriving in many pieces is looked
+/// at once rather than lexed again on every piece.
+pub(crate) struct Window {
+    text: String,     // Always whole chars
+    pending: Vec<u8>, // The start of a char cut in half at the end of the last piece
+    pos: usize,       // Where the next token starts in text
+    line: usize,
+    column: usize,
+    consumed: usize, // Bytes dropped from the front of text so far
+    scan: Scan,
+    scanned: usize, // Where the scan has got to in text
+    ready: usize,   // Tokens starting before this are complete
+    options: ParserOptions,
+}
+
+/// What the scan is in the middle of
+#[derive(Clone, Copy)]
+enum Scan {
+    Between,
+    Number,
+    Literal, // Also JSON5 identifiers
+    String { quote: u8 },
+    Escape { quote: u8 },
+    EscapedCr { quote: u8 }, // After an escaped `\r`, which JSON5 takes together with a `\n`
+    Slash,
+    LineComment,
+    BlockComment { star: bool },
+}
+
+impl Window {
+    pub(crate) fn new(options: &ParserOptions) -> Self {
+        Self {
+            text: String::new(),
+            pending: Vec::new(),
+            pos: 0,
+            line: 1,
+            column: 1,
+            consumed: 0,
+            scan: Scan::Between,
+            scanned: 0,
+            ready: 0,
+            options: options.clone(),
+        }
+    }
+
+    pub(crate) fn options(&self) -> &ParserOptions {
+        &self.options
+    }
+
+    /// Bytes that have arrived but have not been lexed yet
+    pub(crate) fn buffered(&self) -> usize {
+        self.text.len() - self.pos
+    }
+
+    /// Appends the next piece of the input
+    pub(crate) fn push(&mut self, bytes: &[u8]) -> io::Result<()> {
+        if self.pos > 0 {
+            self.text.drain(..self.pos);
+            self.consumed += self.pos;
+            self.scanned -= self.pos;
+            self.ready = self.ready.saturating_sub(self.pos);
+            self.pos = 0;
+        }
+        // A char cut in half stays pending until the rest of it arrives
+        self.pending.extend_from_slice(bytes);
+        let valid = match std::str::from_utf8(&self.pending) {
+            Ok(_) => self.pending.len(),
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(e) => return Err(self.invalid_utf8(e.valid_up_to())),
+        };
+        // Checked just above
+        self.text
+            .push_str(std::str::from_utf8(&self.pending[..valid]).unwrap());
+        self.pending.drain(..valid);
+        Ok(())
+    }
+
+    /// Called once all of the input has arrived, fails when it ends in the middle of a char
+    pub(crate) fn finish(&self) -> io::Result<()> {
+        match self.pending.is_empty() {
+            true => Ok(()),
+            false => Err(self.invalid_utf8(0)),
+        }
+    }
+
+    fn invalid_utf8(&self, valid: usize) -> io::Error {
+        let at = self.consumed + self.text.len() + valid;
+        let message = format!("invalid UTF-8 at byte {}", at);
+        io::Error::new(io::ErrorKind::InvalidData, message)
+    }
+
+    /// Error for reading that failed, at where lexing has got to
+    pub(crate) fn io_error(&self, error: io::Error) -> LexerError {
+        let (start, line, column) = (self.consumed + self.pos, self.line, self.column);
+        LexerError {
+            kind: LexerErrorKinds::Io(Arc::new(error)),
+            line,
+            column,
+            span: Span {
+                start,
+                end: start,
+                start_line: line,
+                start_column: column,
+                end_line: line,
+                end_column: column,
+            },
+        }
+    }
+
+    /// Lexes the next token. None when it could still change once more input arrives, which
+    /// never happens once `eof` says that all of it has.
+    pub(crate) fn next_token(&mut self, eof: bool) -> Option<Result<Token<'static>, LexerError>> {
+        if !eof {
+            self.scan();
+            if self.pos >= self.ready {
+                return None;
+            }
+        }
+        let lexer = Lexer::with_options(&self.text, &self.options);
+        let mut lexer = lexer.resume_at(self.pos, self.line, self.column);
+        let result = lexer.next_token();
+        (self.pos, self.line, self.column) = lexer.position();
+
+        // Spans are relative to the window, move them to where it lies in the input
+        Some(match result {
+            Ok(token) => Ok(Token {
+                kind: token.kind.into_owned(),
+                span: token.span.offset(self.consumed, 0),
+                ..token
+            }),
+            Err(e) => Err(LexerError {
+                span: e.span.offset(self.consumed, 0),
+                ..e
+            }),
+        })
+    }
+
+    /// Scans what has arrived since the last scan
+    fn scan(&mut self) {
+        while let Some(&b) = self.text.as_bytes().get(self.scanned) {
+            if self.step(b) {
+                self.scanned += 1;
+            }
+        }
+    }
+
+    /// Moves the scan on by one byte, false when the byte is to be looked at again in the new
+    /// state. A token counts as complete once every byte the lexer looks at for it has arrived,
+    /// which for numbers and literals includes the byte after them. Where the scan is unsure
+    /// it waits for more, the lexer has the final say.
+    fn step(&mut self, b: u8) -> bool {
+        let i = self.scanned;
+        let (scan, used) = match (self.scan, b) {
+            (Scan::Between, b' ' | b'\t' | b'\n' | b'\r' | 0x0B | 0x0C) => (Scan::Between, true),
+            (Scan::Between, b'"') => (Scan::String { quote: b }, true),
+            (Scan::Between, b'\'') if self.options.allows_single_quotes() => {
+                (Scan::String { quote: b }, true)
+            }
+            (Scan::Between, b'/') if self.options.allows_comments() => (Scan::Slash, true),
+            // Single bytes, including the ones that are unexpected on their own
+            (Scan::Between, b'{' | b'}' | b'[' | b']' | b':' | b',' | b'\'' | b'/') => {
+                self.ready = i + 1;
+                (Scan::Between, true)
+            }
+            (Scan::Between, b'0'..=b'9' | b'-' | b'+' | b'.') => (Scan::Number, true),
+            (Scan::Between, _) => (Scan::Literal, true),
+            (Scan::Number | Scan::Literal, _) if ends_word(b) => {
+                self.ready = i;
+                (Scan::Between, false)
+            }
+            (Scan::Number | Scan::Literal, _) => (self.scan, true),
+            (Scan::String { quote }, _) if b == quote => {
+                self.ready = i + 1;
+                (Scan::Between, true)
+            }
+            (Scan::String { quote }, b'\\') => (Scan::Escape { quote }, true),
+            // Where the lexer gives up on the string
+            (Scan::String { .. }, b'\n' | b'\r') => {
+                self.ready = i + 1;
+                (Scan::Between, true)
+            }
+            (Scan::String { .. }, ..0x20) if !self.options.allows_control_characters() => {
+                self.ready = i + 1;
+                (Scan::Between, true)
+            }
+            (Scan::String { .. }, _) => (self.scan, true),
+            (Scan::Escape { quote }, b'\r') => (Scan::EscapedCr { quote }, true),
+            (Scan::Escape { quote }, _) => (Scan::String { quote }, true),
+            (Scan::EscapedCr { quote }, b'\n') => (Scan::String { quote }, true),
+            (Scan::EscapedCr { quote }, _) => (Scan::String { quote }, false),
+            (Scan::Slash, b'/') => (Scan::LineComment, true),
+            (Scan::Slash, b'*') => (Scan::BlockComment { star: false }, true),
+            // A lone slash, which the lexer reports
+            (Scan::Slash, _) => {
+                self.ready = i;
+                (Scan::Between, false)
+            }
+            (Scan::LineComment, b'\n' | b'\r') => (Scan::Between, true),
+            (Scan::LineComment, _) => (Scan::LineComment, true),
+            (Scan::BlockComment { star: true }, b'/') => (Scan::Between, true),
+            (Scan::BlockComment { .. }, _) => (Scan::BlockComment { star: b == b'*' }, true),
+        };
+        self.scan = scan;
+        used
+    }
+}
+
+/// Bytes no number, literal or identifier goes on over
+fn ends_word(b: u8) -> bool {
+    matches!(
+        b,
+        b' ' | b'\t'
+            | b'\n'
+            | b'\r'
+            | 0x0B
+            | 0x0C
+            | b'{'
+            | b'}'
+            | b'['
+            | b']'
+            | b':'
+            | b','
+            | b'"'
+            | b'\''
+            | b'/'
+    )
+}
+
+/// Lexes the input of a reader, reading the next chunk into the window whenever it runs dry
+pub(crate) struct ReadLexer<R> {
+    reader: R,
+    window: Window,
+    chunk: Vec<u8>,
+    eof: bool,
+    finished: bool,
+}
+
+impl<R: Read> ReadLexer<R> {
+    pub(crate) fn new(reader: R, options: &ParserOptions) -> Self {
+        Self {
+            reader,
+            window: Window::new(options),
+            chunk: Vec::new(),
+            eof: false,
+            finished: false,
+        }
+    }
+
+    fn next_token(&mut self) -> Result<Token<'static>, LexerError> {
+        loop {
+            if let Some(result) = self.window.next_token(self.eof) {
+                return result;
+            }
+            if let Err(e) = self.fill() {
+                return Err(self.window.io_error(e));
+            }
+        }
+    }
+
+    fn fill(&mut self) -> io::Result<()> {
+        // Reads grow along with a token that outgrows the chunk size, so it takes few of them
+        let len = self.window.buffered().max(CHUNK_SIZE);
+        self.chunk.resize(len, 0);
+        let read = loop {
+            match self.reader.read(&mut self.chunk) {
+                Ok(read) => break read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        };
+        if read == 0 {
+            self.eof = true;
+            return self.window.finish();
+        }
+        self.window.push(&self.chunk[..read])
+    }
+}
+
+impl<R: Read> Iterator for ReadLexer<R> {
+    type Item = Result<Token<'static>, LexerError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        let result = self.next_token();
+        self.finished = match &result {
+            Ok(token) => token.kind == TokenKind::EOF,
+            Err(_) => true,
+        };
+        Some(result)
+    }
+}
+
+impl<R: Read> TokenSource<'static> for ReadLexer<R> {
+    fn options(&self) -> &ParserOptions {
+        self.window.options()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_long_tokens_and_positions() {
+        // Strings longer than the chunk size grow the window
+        let long = "é".repeat(CHUNK_SIZE);
+        let input = format!("[\n\"{}\",\n  x]", long);
+        let err = match parse_reader(input.as_bytes()) {
+            Err(ReadError::Parse(e)) => e,
+            result => panic!("{:?}", result),
+        };
+        assert_eq!((err.line(), err.column()), (3, 3));
+        assert_eq!(err.span().start, input.find('x').unwrap());
+
+        let value = parse_reader(format!("\"{}\"", long).as_bytes()).unwrap();
+        assert_eq!(value, JSONValue::String(long.into()));
+    }
+
+    #[test]
+    fn test_read_errors() {
+        let result = parse_reader(&b"[\"\xff\"]"[..]);
+        assert!(matches!(result, Err(ReadError::Io(e)) if e.kind() == io::ErrorKind::InvalidData));
+        // A char cut short by the end of the input
+        assert!(matches!(
+            parse_reader(&b"\"\xc3"[..]),
+            Err(ReadError::Io(_))
+        ));
+
+        struct Failing;
+        impl Read for Failing {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::other("connection reset"))
+            }
+        }
+        let err = parse_reader(Failing).unwrap_err();
+        assert_eq!(err.to_string(), "I/O error: connection reset");
+        // The reader's own error, along with what it wraps
+        struct Wrapping;
+        impl Read for Wrapping {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::other(std::fmt::Error))
+            }
+        }
+        let err = parse_reader(Wrapping).unwrap_err();
+        let source = std::error::Error::source(&err).unwrap();
+        let inner = source
+            .downcast_ref::<io::Error>()
+            .and_then(io::Error::get_ref);
+        assert!(inner.is_some_and(|inner| inner.is::<std::fmt::Error>()));
+
+        // Lexing hands the failure on as an error of its own
+        let mut lexer = ReadLexer::new(Failing, &ParserOptions::default());
+        let err = lexer.next().unwrap().unwrap_err();
+        assert_eq!(err.kind.code(), "io_error");
+        assert_eq!((err.line, err.column), (1, 1));
+        assert!(lexer.next().is_none());
+    }
+}
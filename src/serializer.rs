@@ -0,0 +1,306 @@
+use std::fmt;
+use std::io;
+
+use crate::jsonvalue::JSONValue;
+
+/// Indentation used by the pretty printer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Indent {
+    /// Indent each level with the given number of spaces
+    Spaces(usize),
+    /// Indent each level with a single tab
+    Tabs,
+}
+
+/// Line ending written between lines in pretty output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Newline {
+    #[default]
+    Lf,
+    CrLf,
+}
+
+impl Newline {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Newline::Lf => "\n",
+            Newline::CrLf => "\r\n",
+        }
+    }
+}
+
+/// Controls how a `JSONValue` is written out. Compact by default.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SerializerOptions {
+    indent: Option<Indent>,
+    newline: Newline,
+}
+
+impl SerializerOptions {
+    /// No whitespace at all, `{"a":[1,2]}`
+    pub fn compact() -> Self {
+        Self::default()
+    }
+
+    /// One member per line, indented with two spaces
+    pub fn pretty() -> Self {
+        Self {
+            indent: Some(Indent::Spaces(2)),
+            newline: Newline::Lf,
+        }
+    }
+
+    /// Switches to pretty output using the given indentation
+    pub fn indent(mut self, indent: Indent) -> Self {
+        self.indent = Some(indent);
+        self
+    }
+
+    /// Line ending used in pretty output, ignored for compact output
+    pub fn newline(mut self, newline: Newline) -> Self {
+        self.newline = newline;
+        self
+    }
+
+    pub fn is_pretty(&self) -> bool {
+        self.indent.is_some()
+    }
+}
+
+/// Serializes the value without any whitespace
+pub fn to_string(value: &JSONValue<'_>) -> String {
+    to_string_with(value, &SerializerOptions::compact())
+}
+
+/// Serializes the value with two space indentation
+pub fn to_string_pretty(value: &JSONValue<'_>) -> String {
+    to_string_with(value, &SerializerOptions::pretty())
+}
+
+pub fn to_string_with(value: &JSONValue<'_>, options: &SerializerOptions) -> String {
+    let mut out = String::with_capacity(128);
+    // Writing into a String never fails
+    write_value(&mut out, value, options).expect("writing to a String cannot fail");
+    out
+}
+
+/// Writes the value into any `fmt::Write`, such as a `String` or a `fmt::Formatter`
+pub fn write_value<W: fmt::Write>(
+    out: &mut W,
+    value: &JSONValue<'_>,
+    options: &SerializerOptions,
+) -> fmt::Result {
+    let mut serializer = Serializer {
+        out,
+        options,
+        depth: 0,
+    };
+    serializer.write_value(value)
+}
+
+/// Writes the value into an `io::Write`, such as a file or stdout
+pub fn to_writer<W: io::Write>(
+    writer: W,
+    value: &JSONValue<'_>,
+    options: &SerializerOptions,
+) -> io::Result<()> {
+    let mut adapter = IoAdapter {
+        inner: writer,
+        error: None,
+    };
+    match write_value(&mut adapter, value, options) {
+        Ok(()) => Ok(()),
+        // fmt::Error carries no information, so hand back the io error that caused it
+        Err(_) => Err(adapter
+            .error
+            .unwrap_or_else(|| io::Error::other("formatter error"))),
+    }
+}
+
+struct IoAdapter<W: io::Write> {
+    inner: W,
+    error: Option<io::Error>,
+}
+
+impl<W: io::Write> fmt::Write for IoAdapter<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.inner.write_all(s.as_bytes()).map_err(|e| {
+            self.error = Some(e);
+            fmt::Error
+        })
+    }
+}
+
+struct Serializer<'o, W: fmt::Write> {
+    out: &'o mut W,
+    options: &'o SerializerOptions,
+    depth: usize,
+}
+
+impl<W: fmt::Write> Serializer<'_, W> {
+    fn write_value(&mut self, value: &JSONValue<'_>) -> fmt::Result {
+        match value {
+            JSONValue::Null => self.out.write_str("null"),
+            JSONValue::Bool(true) => self.out.write_str("true"),
+            JSONValue::Bool(false) => self.out.write_str("false"),
+            JSONValue::Number(n) => write!(self.out, "{}", n),
+            JSONValue::String(s) => write_string(self.out, s),
+            JSONValue::Array(arr) => {
+                if arr.is_empty() {
+                    return self.out.write_str("[]");
+                }
+                self.out.write_char('[')?;
+                self.depth += 1;
+                for (i, item) in arr.iter().enumerate() {
+                    if i > 0 {
+                        self.out.write_char(',')?;
+                    }
+                    self.write_newline()?;
+                    self.write_value(item)?;
+                }
+                self.depth -= 1;
+                self.write_newline()?;
+                self.out.write_char(']')
+            }
+            JSONValue::Object(map) => {
+                if map.is_empty() {
+                    return self.out.write_str("{}");
+                }
+                self.out.write_char('{')?;
+                self.depth += 1;
+                for (i, (key, item)) in map.iter().enumerate() {
+                    if i > 0 {
+                        self.out.write_char(',')?;
+                    }
+                    self.write_newline()?;
+                    write_string(self.out, key)?;
+                    self.out.write_char(':')?;
+                    if self.options.is_pretty() {
+                        self.out.write_char(' ')?;
+                    }
+                    self.write_value(item)?;
+                }
+                self.depth -= 1;
+                self.write_newline()?;
+                self.out.write_char('}')
+            }
+        }
+    }
+
+    /// Starts a new line at the current depth, does nothing for compact output
+    fn write_newline(&mut self) -> fmt::Result {
+        let Some(indent) = self.options.indent else {
+            return Ok(());
+        };
+        self.out.write_str(self.options.newline.as_str())?;
+        for _ in 0..self.depth {
+            match indent {
+                Indent::Spaces(n) => {
+                    for _ in 0..n {
+                        self.out.write_char(' ')?;
+                    }
+                }
+                Indent::Tabs => self.out.write_char('\t')?,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn write_string<W: fmt::Write>(out: &mut W, s: &str) -> fmt::Result {
+    out.write_char('"')?;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        let escaped = match c {
+            '"' => "\\\"",
+            '\\' => "\\\\",
+            '\n' => "\\n",
+            '\r' => "\\r",
+            '\t' => "\\t",
+            '\u{0008}' => "\\b",
+            '\u{000C}' => "\\f",
+            c if c < '\u{20}' => "",
+            _ => continue,
+        };
+        // Flush the run of characters that did not need escaping
+        out.write_str(&s[start..i])?;
+        if escaped.is_empty() {
+            write!(out, "\\u{:04x}", c as u32)?;
+        } else {
+            out.write_str(escaped)?;
+        }
+        start = i + c.len_utf8();
+    }
+    out.write_str(&s[start..])?;
+    out.write_char('"')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_json;
+
+    #[test]
+    fn test_compact_output() {
+        let value = parse_json(r#"[1, -2.5, "a", true, false, null, [], {}]"#).unwrap();
+        assert_eq!(to_string(&value), r#"[1,-2.5,"a",true,false,null,[],{}]"#);
+    }
+
+    #[test]
+    fn test_pretty_output() {
+        let value = parse_json(r#"{"a": [1, 2]}"#).unwrap();
+        assert_eq!(
+            to_string_pretty(&value),
+            "{\n  \"a\": [\n    1,\n    2\n  ]\n}"
+        );
+
+        let options = SerializerOptions::pretty()
+            .indent(Indent::Tabs)
+            .newline(Newline::CrLf);
+        assert_eq!(
+            to_string_with(&value, &options),
+            "{\r\n\t\"a\": [\r\n\t\t1,\r\n\t\t2\r\n\t]\r\n}"
+        );
+    }
+
+    #[test]
+    fn test_string_escaping() {
+        let value = JSONValue::String("quote\" slash\\ \n\t\u{0001} é".into());
+        assert_eq!(to_string(&value), r#""quote\" slash\\ \n\t\u0001 é""#);
+    }
+
+    #[test]
+    fn test_number_round_trip() {
+        for input in [
+            "0",
+            "1.0",
+            "100",
+            "9007199254740993",
+            "-0",
+            "1e400",
+            "-1e400",
+            "0.1",
+            "1.5e300",
+            "123456789012345678",
+            "1e-7",
+        ] {
+            let value = parse_json(input).unwrap();
+            let output = to_string(&value);
+            assert_eq!(
+                parse_json(&output).unwrap(),
+                value,
+                "{} -> {}",
+                input,
+                output
+            );
+        }
+    }
+
+    #[test]
+    fn test_to_writer() {
+        let value = parse_json(r#"{"a": null}"#).unwrap();
+        let mut buf = Vec::new();
+        to_writer(&mut buf, &value, &SerializerOptions::compact()).unwrap();
+        assert_eq!(buf, br#"{"a":null}"#);
+    }
+}
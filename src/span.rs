@@ -0,0 +1,57 @@
+/// A range of the input. `start` and `end` are byte offsets with `end` exclusive, so
+/// `&input[span.start..span.end]` is the exact text covered.
+///
+/// Lines and columns are 1-based and columns count chars, like everywhere else in the crate.
+/// `end_line` and `end_column` give the position just past the last char.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub start_line: usize,
+    pub start_column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+}
+
+impl Span {
+    /// Length in bytes
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// From the start of `self` to the end of `end`, which comes later in the input
+    pub fn to(self, end: Span) -> Span {
+        Span {
+            end: end.end,
+            end_line: end.end_line,
+            end_column: end.end_column,
+            ..self
+        }
+    }
+
+    /// Moves the span forward by `bytes` and `lines`, for input parsed on its own that starts
+    /// at the beginning of a line further into a larger text
+    pub(crate) fn offset(self, bytes: usize, lines: usize) -> Span {
+        Span {
+            start: self.start + bytes,
+            end: self.end + bytes,
+            start_line: self.start_line + lines,
+            end_line: self.end_line + lines,
+            ..self
+        }
+    }
+
+    /// From the start of `self` up to where `next` begins
+    pub fn until(self, next: Span) -> Span {
+        Span {
+            end: next.start,
+            end_line: next.start_line,
+            end_column: next.start_column,
+            ..self
+        }
+    }
+}
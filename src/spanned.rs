@@ -0,0 +1,293 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    jsonvalue::JSONValue, number::Number, object::JSONObject, options::DuplicateKeys,
+    parser::BuildValue, span::Span,
+};
+
+/// A parsed value that remembers where in the input it came from, see `parse_json_spanned`.
+/// Meant for tools that report problems in a document after parsing it, like config validators.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpannedValue<'a> {
+    /// The whole value, including the quotes of strings and the brackets of arrays and objects
+    pub span: Span,
+    pub kind: SpannedKind<'a>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpannedKind<'a> {
+    Array(Vec<SpannedValue<'a>>),
+    Bool(bool),
+    Null,
+    Number(Number<'a>),
+    /// Members in source order, with repeated keys already resolved by `DuplicateKeys`. With
+    /// `CollectAll` every occurrence stays a member of its own, and lookups find the last.
+    Object(Vec<SpannedMember<'a>>),
+    String(Cow<'a, str>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpannedMember<'a> {
+    pub key: Cow<'a, str>,
+    /// The key including its quotes
+    pub key_span: Span,
+    pub value: SpannedValue<'a>,
+}
+
+impl<'a> SpannedValue<'a> {
+    /// Looks up a value by JSON Pointer (RFC 6901), such as `/servers/0/port`. The empty
+    /// string points at the value itself.
+    pub fn pointer(&self, pointer: &str) -> Option<&SpannedValue<'a>> {
+        self.walk(pointer).map(|(_, value)| value)
+    }
+
+    /// Span of the key of the object member a JSON Pointer leads to. None for array elements,
+    /// the root, and pointers that lead nowhere.
+    pub fn key_span(&self, pointer: &str) -> Option<Span> {
+        self.walk(pointer)?.0
+    }
+
+    /// Value of an object member by key
+    pub fn get(&self, key: &str) -> Option<&SpannedValue<'a>> {
+        match &self.kind {
+            SpannedKind::Object(members) => members
+                .iter()
+                .rfind(|member| member.key == key)
+                .map(|member| &member.value),
+            _ => None,
+        }
+    }
+
+    /// Drops the spans
+    pub fn into_value(self) -> JSONValue<'a> {
+        match self.kind {
+            SpannedKind::Array(items) => {
+                JSONValue::Array(items.into_iter().map(SpannedValue::into_value).collect())
+            }
+            SpannedKind::Bool(b) => JSONValue::Bool(b),
+            SpannedKind::Null => JSONValue::Null,
+            SpannedKind::Number(n) => JSONValue::Number(n),
+            SpannedKind::Object(members) => {
+                // Keys only repeat with CollectAll, so gather them the same way
+                let mut object = JSONObject::with_capacity(members.len());
+                let mut collected = HashSet::new();
+                for member in members {
+                    let key = (member.key, member.key_span);
+                    let value = member.value.into_value();
+                    let policy = DuplicateKeys::CollectAll;
+                    JSONValue::insert(&mut object, key, value, policy, &mut collected);
+                }
+                JSONValue::Object(object)
+            }
+            SpannedKind::String(s) => JSONValue::String(s),
+        }
+    }
+
+    /// Follows a pointer, returning the value along with the span of the key it was reached by
+    fn walk(&self, pointer: &str) -> Option<(Option<Span>, &SpannedValue<'a>)> {
+        if pointer.is_empty() {
+            return Some((None, self));
+        }
+        let mut current = (None, self);
+        for token in pointer.strip_prefix('/')?.split('/') {
+            let token = unescape_pointer_token(token);
+            current = match &current.1.kind {
+                SpannedKind::Object(members) => {
+                    let member = members.iter().rfind(|member| member.key == token)?;
+                    (Some(member.key_span), &member.value)
+                }
+                SpannedKind::Array(items) => (None, items.get(array_index(&token)?)?),
+                _ => return None,
+            };
+        }
+        Some(current)
+    }
+}
+
+/// `~1` stands for `/` and `~0` for `~`, decoded in that order
+pub(crate) fn unescape_pointer_token(token: &str) -> Cow<'_, str> {
+    if token.contains('~') {
+        Cow::Owned(token.replace("~1", "/").replace("~0", "~"))
+    } else {
+        Cow::Borrowed(token)
+    }
+}
+
+/// Array indices in pointers are plain decimal numbers without leading zeros
+pub(crate) fn array_index(token: &str) -> Option<usize> {
+    let digits_only = !token.is_empty() && token.bytes().all(|b| b.is_ascii_digit());
+    if !digits_only || (token.len() > 1 && token.starts_with('0')) {
+        return None;
+    }
+    token.parse().ok()
+}
+
+/// Members of an object being parsed, indexed by key to resolve duplicates
+pub(crate) struct SpannedObject<'a> {
+    members: Vec<SpannedMember<'a>>,
+    index: HashMap<Cow<'a, str>, usize>,
+}
+
+impl<'a> BuildValue<'a> for SpannedValue<'a> {
+    type Members = SpannedObject<'a>;
+
+    fn scalar(value: JSONValue<'a>, span: Span) -> Self {
+        let kind = match value {
+            JSONValue::Bool(b) => SpannedKind::Bool(b),
+            JSONValue::Null => SpannedKind::Null,
+            JSONValue::Number(n) => SpannedKind::Number(n),
+            JSONValue::String(s) => SpannedKind::String(s),
+            JSONValue::Array(_) | JSONValue::Object(_) => unreachable!("not a scalar"),
+        };
+        SpannedValue { span, kind }
+    }
+
+    fn array(items: Vec<Self>, span: Span) -> Self {
+        SpannedValue {
+            span,
+            kind: SpannedKind::Array(items),
+        }
+    }
+
+    fn new_members() -> Self::Members {
+        SpannedObject {
+            members: Vec::new(),
+            index: HashMap::new(),
+        }
+    }
+
+    fn index_of(object: &Self::Members, key: &str) -> Option<usize> {
+        object.index.get(key).copied()
+    }
+
+    fn insert(
+        object: &mut Self::Members,
+        (key, key_span): (Cow<'a, str>, Span),
+        value: Self,
+        policy: DuplicateKeys,
+        _collected: &mut HashSet<usize>,
+    ) {
+        let Some(&i) = object.index.get(&key) else {
+            object.index.insert(key.clone(), object.members.len());
+            object.members.push(SpannedMember {
+                key,
+                key_span,
+                value,
+            });
+            return;
+        };
+        match policy {
+            // The member points at the key the value came from
+            DuplicateKeys::LastWins | DuplicateKeys::Error => {
+                let member = &mut object.members[i];
+                member.key_span = key_span;
+                member.value = value;
+            }
+            DuplicateKeys::FirstWins => {}
+            // Each occurrence keeps its own spans, `into_value` gathers them
+            DuplicateKeys::CollectAll => object.members.push(SpannedMember {
+                key,
+                key_span,
+                value,
+            }),
+        }
+    }
+
+    fn object(object: Self::Members, span: Span) -> Self {
+        SpannedValue {
+            span,
+            kind: SpannedKind::Object(object.members),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::ParserOptions;
+    use crate::parser::{parse_json, parse_json_spanned, parse_json_spanned_with, parse_json_with};
+
+    fn text(input: &str, span: Span) -> &str {
+        &input[span.start..span.end]
+    }
+
+    #[test]
+    fn test_spans_of_values_and_keys() {
+        let input = "{\n  \"servers\": [\n    {\"host\": \"a\", \"port\": 70000}\n  ]\n}";
+        let tree = parse_json_spanned(input).unwrap();
+        assert_eq!(
+            tree.span,
+            Span {
+                start: 0,
+                end: input.len(),
+                start_line: 1,
+                start_column: 1,
+                end_line: 5,
+                end_column: 2
+            }
+        );
+
+        let port = tree.pointer("/servers/0/port").unwrap();
+        assert_eq!(text(input, port.span), "70000");
+        assert_eq!((port.span.start_line, port.span.start_column), (3, 27));
+        let key = tree.key_span("/servers/0/port").unwrap();
+        assert_eq!(text(input, key), "\"port\"");
+
+        let server = tree.pointer("/servers/0").unwrap();
+        assert_eq!(
+            text(input, server.span),
+            "{\"host\": \"a\", \"port\": 70000}"
+        );
+        assert_eq!(tree.key_span("/servers/0"), None);
+        assert_eq!(tree.get("servers").map(|v| v.span.start_line), Some(2));
+    }
+
+    #[test]
+    fn test_pointer_syntax() {
+        let input = r#"{"a/b": {"m~n": [1, 2]}, "": 0}"#;
+        let tree = parse_json_spanned(input).unwrap();
+        assert_eq!(tree.pointer("").map(|v| v.span), Some(tree.span));
+        assert_eq!(text(input, tree.pointer("/a~1b/m~0n/1").unwrap().span), "2");
+        assert_eq!(text(input, tree.pointer("/").unwrap().span), "0");
+        assert!(tree.pointer("a~1b").is_none());
+        assert!(tree.pointer("/a~1b/m~0n/01").is_none());
+        assert!(tree.pointer("/a~1b/m~0n/2").is_none());
+        assert!(tree.pointer("/a~1b/m~0n/-").is_none());
+    }
+
+    #[test]
+    fn test_into_value_matches_plain_parse() {
+        let input = r#"{"a": [1, 2.5, "x\n", null, true], "b": {"c": {}}, "a": false}"#;
+        let tree = parse_json_spanned(input).unwrap();
+        assert_eq!(tree.clone().into_value(), parse_json(input).unwrap());
+
+        // Duplicates follow the same policy, the member points at the winning key
+        let key = tree.key_span("/a").unwrap();
+        assert_eq!(key.start, input.rfind("\"a\"").unwrap());
+
+        let options = ParserOptions::new().duplicate_keys(DuplicateKeys::CollectAll);
+        let tree = parse_json_spanned_with(input, &options).unwrap();
+        assert_eq!(
+            tree.clone().into_value(),
+            parse_json_with(input, &options).unwrap()
+        );
+        // Every occurrence keeps its own spans, lookups find the last
+        let SpannedKind::Object(members) = &tree.kind else {
+            panic!("not an object");
+        };
+        let spans: Vec<_> = members
+            .iter()
+            .filter(|member| member.key == "a")
+            .map(|member| (member.key_span.start, text(input, member.value.span)))
+            .collect();
+        assert_eq!(
+            spans,
+            [
+                (1, r#"[1, 2.5, "x\n", null, true]"#),
+                (input.rfind("\"a\"").unwrap(), "false")
+            ]
+        );
+        assert_eq!(text(input, tree.pointer("/a").unwrap().span), "false");
+    }
+}
@@ -0,0 +1,135 @@
+use crate::{
+    errors::ParserError, jsonvalue::JSONValue, lexer::Lexer, options::ParserOptions, parser::Parser,
+};
+
+/// Iterates over values that follow each other in one input, such as `{"a":1}{"a":2}` or
+/// `1 2 3`, with or without whitespace in between. Yields nothing more after an error.
+pub struct JsonStream<'a> {
+    parser: Parser<'a, Lexer<'a>>,
+    failed: bool,
+}
+
+/// Iterates over the records of an RFC 7464 JSON text sequence, values that each start with
+/// the RS control character (0x1E) and usually end with a newline. A record that fails to
+/// parse, for instance because it was cut short, is reported and skipped.
+pub struct JsonSeq<'a> {
+    parser: Parser<'a, Lexer<'a>>,
+}
+
+impl<'a> JsonStream<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Self::with_options(input, &ParserOptions::default())
+    }
+
+    pub fn with_options(input: &'a str, options: &ParserOptions) -> Self {
+        let lexer = Lexer::with_options(input, options);
+        Self {
+            parser: Parser::from_tokens(lexer),
+            failed: false,
+        }
+    }
+}
+
+impl<'a> Iterator for JsonStream<'a> {
+    type Item = Result<JSONValue<'a>, ParserError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let result = self.parser.parse_next()?;
+        self.failed = result.is_err();
+        Some(result)
+    }
+}
+
+impl<'a> JsonSeq<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Self::with_options(input, &ParserOptions::default())
+    }
+
+    pub fn with_options(input: &'a str, options: &ParserOptions) -> Self {
+        let lexer = Lexer::with_options(input, options).record_separators(true);
+        Self {
+            parser: Parser::from_tokens(lexer),
+        }
+    }
+}
+
+impl<'a> Iterator for JsonSeq<'a> {
+    type Item = Result<JSONValue<'a>, ParserError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.parser.parse_next_record()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collect<'a>(
+        values: impl Iterator<Item = Result<JSONValue<'a>, ParserError>>,
+    ) -> Vec<String> {
+        values
+            .map(|result| match result {
+                Ok(value) => value.to_string(),
+                Err(e) => e.code().to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_concatenated_values() {
+        let input = "{\"a\":1}{\"a\":2}[3]\"x\"4 true\n null  ";
+        assert_eq!(
+            collect(JsonStream::new(input)),
+            [
+                r#"{"a":1}"#,
+                r#"{"a":2}"#,
+                "[3]",
+                "\"x\"",
+                "4",
+                "true",
+                "null"
+            ]
+        );
+        assert!(JsonStream::new(" \n").next().is_none());
+
+        // Stops at the first error
+        assert_eq!(
+            collect(JsonStream::new("[1] [2,] [3]")),
+            ["[1]", "trailing_comma"]
+        );
+    }
+
+    #[test]
+    fn test_record_sequences() {
+        let input = "\x1e{\"a\":1}\n\x1e{\"a\":\x1e[2]\n\x1e3\x1e\x1e4\n\x1e\"cut\x1etrue \x1e";
+        assert_eq!(
+            collect(JsonSeq::new(input)),
+            [
+                r#"{"a":1}"#,
+                "unexpected_token",
+                "[2]",
+                "truncated_record",
+                "4",
+                "unclosed_string",
+                "true"
+            ]
+        );
+
+        // Errors point into the whole input
+        let err = JsonSeq::new(input).nth(3).unwrap().unwrap_err();
+        assert_eq!(
+            (err.span().start, err.line()),
+            (input.find('3').unwrap(), 3)
+        );
+
+        // Every record has to start with RS
+        assert_eq!(
+            collect(JsonSeq::new("[1]\n\x1e[2]\n")),
+            ["expected_token", "[2]"]
+        );
+    }
+}
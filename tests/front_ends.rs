@@ -0,0 +1,164 @@
+//! Every way of parsing a document has to give what `parse_json_with` gives for it, the value
+//! when it is valid and the same error when it is not.
+
+use std::{
+    convert::Infallible,
+    io::{self, Read},
+};
+
+use rust_json_parser::{
+    DuplicateKeys, Handler, JSONValue, ParserOptions, PullParser, PushParser,
+    errors::{HandlerError, ParserError, ReadError},
+    parse_json_events_with, parse_json_with, parse_lazy_with, parse_reader_with,
+};
+
+const INPUTS: &[&str] = &[
+    r#"{"a": [1, -2.5e3, true, false, null, {}], "b": "é😀\n", "ü": [[]]}"#,
+    "  12345678901234567890  ",
+    "[\"\\ud83d\\ude00\", \"\\ud83d\\u0041\"]",
+    "[1, 2,]",
+    "[1, 2,, 3]",
+    "[1 2]",
+    r#"{"a": 1,}"#,
+    r#"{"a" 1}"#,
+    r#"{"a": 1 "b": 2}"#,
+    r#"{"a": 1, "a": 2}"#,
+    r#"{"a": tru}"#,
+    "[[[[1]]]]",
+    "[1] 2",
+    "[1,\n 2",
+    "\"unclosed",
+    "",
+    "{a: 'b', c: [Infinity, +1,], // comment\n}",
+    "/* a */ [.5, -Infinity, 0x1F, 'a\\\r\nb', \"\\x41\" /**/] // end",
+    "[1 / 2]",
+    "[1, 2] /* unclosed",
+];
+
+fn options() -> [ParserOptions; 6] {
+    [
+        ParserOptions::new(),
+        ParserOptions::new().json5(true).max_depth(3),
+        ParserOptions::new()
+            .comments(true)
+            .arbitrary_precision(true),
+        ParserOptions::new().duplicate_keys(DuplicateKeys::Error),
+        ParserOptions::new().duplicate_keys(DuplicateKeys::CollectAll),
+        ParserOptions::new().max_depth(2),
+    ]
+}
+
+/// Runs `check` on every input under every set of options, along with what `parse_json_with`
+/// gives for it
+fn for_each_case(mut check: impl FnMut(&str, &ParserOptions, &Result<JSONValue, ParserError>)) {
+    for options in &options() {
+        for input in INPUTS {
+            check(input, options, &parse_json_with(input, options));
+        }
+    }
+}
+
+fn assert_same<T: std::fmt::Debug>(
+    result: Result<T, ParserError>,
+    expected: &Result<JSONValue, ParserError>,
+    same_value: impl FnOnce(&T, &JSONValue) -> bool,
+    input: &str,
+) {
+    match (result, expected) {
+        (Ok(value), Ok(expected)) if same_value(&value, expected) => {}
+        (Err(e), Err(expected)) => assert_eq!(&e, expected, "{}", input),
+        (result, expected) => panic!("{:?} for {}, expected {:?}", result, input, expected),
+    }
+}
+
+/// Hands out the input a few bytes per read, so tokens and chars get cut at every point
+struct Trickle<'a> {
+    input: &'a [u8],
+    step: usize,
+}
+
+impl Read for Trickle<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.step.min(buf.len()).min(self.input.len());
+        buf[..len].copy_from_slice(&self.input[..len]);
+        self.input = &self.input[len..];
+        Ok(len)
+    }
+}
+
+fn parse_error(error: ReadError) -> ParserError {
+    match error {
+        ReadError::Parse(e) => e,
+        e => panic!("{:?}", e),
+    }
+}
+
+#[test]
+fn test_reader() {
+    for_each_case(|input, options, expected| {
+        for step in [1, 2, 3, 7, 64 * 1024] {
+            let reader = Trickle {
+                input: input.as_bytes(),
+                step,
+            };
+            let result = parse_reader_with(reader, options).map_err(parse_error);
+            assert_same(result, expected, |value, expected| value == expected, input);
+        }
+    });
+}
+
+#[test]
+fn test_push_parser() {
+    for_each_case(|input, options, expected| {
+        for step in [1, 2, 5, input.len().max(1)] {
+            let mut parser = PushParser::with_options(options);
+            let result = input
+                .as_bytes()
+                .chunks(step)
+                .try_for_each(|piece| parser.feed(piece))
+                .and_then(|()| parser.finish())
+                .map_err(parse_error);
+            assert_same(result, expected, |value, expected| value == expected, input);
+        }
+    });
+}
+
+#[test]
+fn test_pull_parser() {
+    for_each_case(|input, options, expected| {
+        let mut parser = PullParser::with_options(input, options);
+        let result = parser.read_value().and_then(|value| {
+            // Fails unless only the end of the input follows
+            parser.next_event()?;
+            Ok(value.expect("documents start with a value"))
+        });
+        assert_same(result, expected, |value, expected| value == expected, input);
+    });
+}
+
+#[test]
+fn test_handler() {
+    struct Ignore;
+    impl Handler<'_> for Ignore {
+        type Error = Infallible;
+    }
+
+    for_each_case(|input, options, expected| {
+        let result = parse_json_events_with(input, options, &mut Ignore).map_err(|e| match e {
+            HandlerError::Parse(e) => e,
+            HandlerError::Handler(e) => match e {},
+        });
+        assert_same(result, expected, |_, _| true, input);
+    });
+}
+
+#[test]
+fn test_lazy() {
+    // The skim only rejects some malformed input, parsing what it accepts checks the rest
+    for_each_case(
+        |input, options, expected| match parse_lazy_with(input, options) {
+            Ok(doc) => assert_eq!(&doc.parse(), expected, "{}", input),
+            Err(e) => assert!(expected.is_err(), "{:?} for {}", e, input),
+        },
+    );
+}